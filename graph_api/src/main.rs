@@ -1,8 +1,12 @@
 use axum::{
-    http::{Method, StatusCode},
+    extract::Query,
+    http::{header, Method, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Router,
 };
+use graph_core::{Format, RenderOptions, Theme};
+use serde::Deserialize;
 use tower::ServiceBuilder;
 use tower_http::cors::{Any, CorsLayer};
 
@@ -10,10 +14,98 @@ async fn status() -> (StatusCode, String) {
     (StatusCode::OK, String::from("OK"))
 }
 
-async fn graph(body: String) -> (StatusCode, String) {
-    match graph_core::generate_graph(&body) {
-        Ok(result) => (StatusCode::OK, result),
-        Err(_) => (StatusCode::NOT_ACCEPTABLE, String::from("Invalid input")),
+/// The query parameters accepted when rendering a graph
+#[derive(Deserialize)]
+struct GraphQuery {
+    /// The name of a built-in theme, overriding the theme selected by the graph
+    theme: Option<String>,
+    /// The name of a built-in theme to switch to when a dark color scheme is preferred
+    dark_theme: Option<String>,
+    /// The format of the response by its extension, SVG by default
+    format: Option<String>,
+    /// The number of pixels per unit of the graph in raster images
+    scale: Option<f32>,
+}
+
+fn find_theme(name: Option<&str>) -> Result<Option<Theme>, String> {
+    name.map(|name| {
+        Theme::named(name).ok_or_else(|| {
+            format!(
+                "unknown theme `{name}`, expected one of {}",
+                Theme::NAMES.join(", ")
+            )
+        })
+    })
+    .transpose()
+}
+
+/// The largest scale raster images can be rendered at
+const MAX_SCALE: f32 = 10.0;
+
+fn find_scale(scale: Option<f32>) -> Result<f32, String> {
+    match scale {
+        Some(scale) if !(scale > 0.0 && scale <= MAX_SCALE) => Err(format!(
+            "invalid scale `{scale}`, expected a number greater than 0 and at most {MAX_SCALE}"
+        )),
+        scale => Ok(scale.unwrap_or(1.0)),
+    }
+}
+
+fn find_format(name: Option<&str>) -> Result<Format, String> {
+    match name {
+        Some(name) => Format::from_extension(name).ok_or_else(|| {
+            let names = Format::ALL.map(|format| format.extension());
+            format!(
+                "unknown format `{name}`, expected one of {}",
+                names.join(", ")
+            )
+        }),
+        None => Ok(Format::Svg),
+    }
+}
+
+async fn graph(Query(query): Query<GraphQuery>, body: String) -> Response {
+    let (theme, dark_theme, format, scale) = match (
+        find_theme(query.theme.as_deref()),
+        find_theme(query.dark_theme.as_deref()),
+        find_format(query.format.as_deref()),
+        find_scale(query.scale),
+    ) {
+        (Ok(theme), Ok(dark_theme), Ok(format), Ok(scale)) => (theme, dark_theme, format, scale),
+        (Err(error), _, _, _)
+        | (_, Err(error), _, _)
+        | (_, _, Err(error), _)
+        | (_, _, _, Err(error)) => return (StatusCode::BAD_REQUEST, error).into_response(),
+    };
+    let options = RenderOptions {
+        theme,
+        dark_theme,
+        scale,
+        ..RenderOptions::default()
+    };
+
+    match graph_core::parse_with_diagnostics(&body) {
+        graph_core::ParseResult {
+            graph: Some(graph),
+            errors,
+        } if errors.is_empty() => match graph_core::render(&graph, format, &options) {
+            Ok(document) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, format.mime_type())],
+                document,
+            )
+                .into_response(),
+            Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response(),
+        },
+        graph_core::ParseResult { errors, .. } => (
+            StatusCode::NOT_ACCEPTABLE,
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+            .into_response(),
     }
 }
 
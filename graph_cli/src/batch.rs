@@ -0,0 +1,289 @@
+use anyhow::{bail, Context, Result};
+use graph_core::{Format, RenderOptions};
+use rayon::prelude::*;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Component, Path, PathBuf},
+    process::ExitCode,
+};
+
+use crate::{read_input, render, Arguments};
+
+/// What happened to a file when rendering a batch
+enum Outcome {
+    Rendered,
+    /// The output was newer than the input, so it was left as it is
+    UpToDate,
+    Failed,
+}
+
+/// Renders every .graph file found from the inputs into the output directory in parallel,
+/// printing a summary. Fails if any of the files can't be rendered
+pub(crate) fn render_all(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    args: &Arguments,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<ExitCode> {
+    let sources = sources(inputs, out_dir, format)?;
+    if sources.is_empty() {
+        bail!("Could not find any .graph files to render");
+    }
+
+    let outcomes = sources
+        .par_iter()
+        .map(|(output, input)| {
+            if !args.force && is_up_to_date(input, output) {
+                return Outcome::UpToDate;
+            }
+            match render_file(input, output, format, options) {
+                Ok(true) => Outcome::Rendered,
+                Ok(false) => Outcome::Failed,
+                Err(error) => {
+                    eprintln!("error: {error:#}");
+                    Outcome::Failed
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let count = |expected: fn(&Outcome) -> bool| {
+        outcomes.iter().filter(|outcome| expected(outcome)).count()
+    };
+    let rendered = count(|outcome| matches!(outcome, Outcome::Rendered));
+    let up_to_date = count(|outcome| matches!(outcome, Outcome::UpToDate));
+    let failed = count(|outcome| matches!(outcome, Outcome::Failed));
+    eprintln!("rendered {rendered} files, {up_to_date} up to date, {failed} failed");
+
+    match failed {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// Renders a file, returning whether the graph had no errors
+fn render_file(
+    input: &Path,
+    output: &Path,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<bool> {
+    let (content, input_name) = read_input(Some(input))?;
+    let Ok(document) = render(&content, &input_name, format, options) else {
+        return Ok(false);
+    };
+
+    if let Some(directory) = output.parent() {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Could not create directory `{}`", directory.display()))?;
+    }
+    fs::write(output, document)
+        .with_context(|| format!("Could not write to output file at `{}`", output.display()))?;
+    Ok(true)
+}
+
+/// Whether the output was modified after the input
+fn is_up_to_date(input: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(input), modified(output)) {
+        (Ok(input), Ok(output)) => output > input,
+        _ => false,
+    }
+}
+
+/// Finds the files to render from paths to .graph files, directories containing them and glob
+/// patterns. Returns the input for each output path, which keeps the path of the input relative
+/// to the directory or the start of the pattern it was found with
+fn sources(
+    inputs: &[PathBuf],
+    out_dir: &Path,
+    format: Format,
+) -> Result<BTreeMap<PathBuf, PathBuf>> {
+    let mut sources = BTreeMap::<PathBuf, PathBuf>::new();
+    for input in inputs {
+        for (path, relative) in find(input)? {
+            let output = out_dir.join(relative).with_extension(format.extension());
+            match sources.get(&output) {
+                Some(existing) if *existing != path => bail!(
+                    "`{}` and `{}` would both be rendered to `{}`",
+                    existing.display(),
+                    path.display(),
+                    output.display()
+                ),
+                _ => sources.insert(output, path),
+            };
+        }
+    }
+    Ok(sources)
+}
+
+/// The files found from an input and their paths relative to it
+pub(crate) fn find(input: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    if input.is_dir() {
+        let mut files = Vec::new();
+        for entry in walkdir::WalkDir::new(input).sort_by_file_name() {
+            let entry =
+                entry.with_context(|| format!("Could not read directory `{}`", input.display()))?;
+            let path = entry.path();
+            if entry.file_type().is_file() && path.extension().is_some_and(|e| e == "graph") {
+                let relative = path.strip_prefix(input)?.to_path_buf();
+                files.push((path.to_path_buf(), relative));
+            }
+        }
+        return Ok(files);
+    }
+
+    let pattern = input.to_string_lossy();
+    if input.exists() || !pattern.contains(['*', '?', '[']) {
+        let file_name = input
+            .file_name()
+            .with_context(|| format!("`{}` is not a file", input.display()))?;
+        return Ok(vec![(input.to_path_buf(), PathBuf::from(file_name))]);
+    }
+
+    // The directories before the first wildcard
+    let base = input
+        .components()
+        .take_while(|component| match component {
+            Component::Normal(name) => !name.to_string_lossy().contains(['*', '?', '[']),
+            _ => true,
+        })
+        .collect::<PathBuf>();
+    let mut files = Vec::new();
+    let paths = glob::glob(&pattern).with_context(|| format!("Invalid pattern `{pattern}`"))?;
+    for path in paths {
+        let path = path?;
+        if path.is_file() {
+            let relative = path.strip_prefix(&base)?.to_path_buf();
+            files.push((path, relative));
+        }
+    }
+    if files.is_empty() {
+        bail!("No files match `{pattern}`");
+    }
+    Ok(files)
+}
+
+/// The files found from each of the inputs, where `-` is stdin. A file found from more than one
+/// input, such as a directory and a pattern, is only returned once
+pub(crate) fn find_all(inputs: &[PathBuf]) -> Result<Vec<Option<PathBuf>>> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        match input == Path::new("-") {
+            true => paths.push(None),
+            false => paths.extend(find(input)?.into_iter().map(|(path, _)| Some(path))),
+        }
+    }
+    let mut seen = HashSet::new();
+    paths.retain(|path| seen.insert(path.clone()));
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    /// A directory with .graph files at the top and in a subdirectory, and a file that isn't one
+    fn files() -> TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        fs::create_dir(directory.path().join("sub")).unwrap();
+        for path in ["a.graph", "sub/b.graph", "sub/a.graph", "notes.txt"] {
+            fs::write(directory.path().join(path), "graph\n  a --> b").unwrap();
+        }
+        directory
+    }
+
+    #[rstest]
+    #[case("", &["a.graph", "sub/a.graph", "sub/b.graph"])]
+    #[case("sub", &["a.graph", "b.graph"])]
+    #[case("a.graph", &["a.graph"])]
+    #[case("sub/b.graph", &["b.graph"])]
+    #[case("*.graph", &["a.graph"])]
+    #[case("**/*.graph", &["a.graph", "sub/a.graph", "sub/b.graph"])]
+    #[case("sub/*.graph", &["a.graph", "b.graph"])]
+    #[case("s*/b.graph", &["sub/b.graph"])]
+    fn test_find(#[case] input: &str, #[case] expected: &[&str]) {
+        let directory = files();
+        let mut found = find(&directory.path().join(input)).unwrap();
+        found.sort();
+
+        let relative = found
+            .iter()
+            .map(|(_, relative)| relative.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            relative,
+            expected.iter().map(PathBuf::from).collect::<Vec<_>>()
+        );
+        for (path, relative) in found {
+            assert!(path.ends_with(relative));
+        }
+    }
+
+    #[rstest]
+    #[case("*.svg")]
+    #[case("missing/**/*.graph")]
+    fn test_find_no_matches(#[case] input: &str) {
+        let directory = files();
+        assert!(find(&directory.path().join(input)).is_err());
+    }
+
+    #[test]
+    fn test_find_all() {
+        let directory = files();
+        let inputs = [
+            directory.path().join("sub"),
+            PathBuf::from("-"),
+            directory.path().join("**/a.graph"),
+            PathBuf::from("-"),
+        ];
+        let found = find_all(&inputs).unwrap();
+
+        let expected = [
+            Some(directory.path().join("sub/a.graph")),
+            Some(directory.path().join("sub/b.graph")),
+            None,
+            Some(directory.path().join("a.graph")),
+        ];
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn test_sources() {
+        let directory = files();
+        let out_dir = Path::new("out");
+        // A file found from more than one input is only rendered once
+        let inputs = [
+            directory.path().to_path_buf(),
+            directory.path().join("a.graph"),
+        ];
+        let sources = sources(&inputs, out_dir, Format::Png).unwrap();
+
+        let outputs = sources.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(
+            outputs,
+            ["out/a.png", "out/sub/a.png", "out/sub/b.png"].map(PathBuf::from)
+        );
+        assert_eq!(
+            sources[Path::new("out/sub/b.png")],
+            directory.path().join("sub/b.graph")
+        );
+    }
+
+    #[test]
+    fn test_sources_collision() {
+        let directory = files();
+        let inputs = [
+            directory.path().join("a.graph"),
+            directory.path().join("sub"),
+        ];
+        let error = sources(&inputs, Path::new("out"), Format::Svg).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("would both be rendered to `out/a.svg`"));
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use graph_core::Error;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::{io, path::PathBuf, process::ExitCode};
+
+use crate::{batch, diagnostic, read_input, write_stdout};
+
+/// How the errors found by the check subcommand are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CheckFormat {
+    /// Diagnostics with the lines they refer to, for people
+    Human,
+    /// An array of errors on stdout, for editors and CI
+    Json,
+}
+
+/// A file that was checked and the errors found in it
+struct Checked {
+    name: String,
+    content: String,
+    errors: Vec<Error>,
+}
+
+/// Parses and validates every .graph file found from the inputs without rendering them, printing
+/// the errors in them. Fails if there are any
+pub(crate) fn check(inputs: &[PathBuf], format: CheckFormat) -> Result<ExitCode> {
+    let paths = batch::find_all(inputs)?;
+
+    let checked = paths
+        .par_iter()
+        .map(|path| {
+            let (content, name) = read_input(path.as_deref())?;
+            let errors = graph_core::check(&content);
+            Ok(Checked {
+                name,
+                content,
+                errors,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let error_count = checked.iter().map(|file| file.errors.len()).sum::<usize>();
+
+    match format {
+        CheckFormat::Human => {
+            for file in checked.iter() {
+                for error in file.errors.iter() {
+                    eprintln!("{}", diagnostic::format(error, &file.content, &file.name));
+                }
+            }
+            eprintln!(
+                "checked {} files, found {error_count} errors",
+                checked.len()
+            );
+        }
+        CheckFormat::Json => {
+            let errors = checked
+                .iter()
+                .flat_map(|file| {
+                    file.errors
+                        .iter()
+                        .map(|error| to_json(error, &file.content, &file.name))
+                })
+                .collect::<Vec<_>>();
+            let output = format!("{}\n", Value::Array(errors));
+            write_stdout(&mut io::stdout().lock(), output.as_bytes())?;
+        }
+    }
+
+    match error_count {
+        0 => Ok(ExitCode::SUCCESS),
+        _ => Ok(ExitCode::FAILURE),
+    }
+}
+
+/// An error as a JSON object. Lines and columns are counted from 1 and the end is exclusive,
+/// `start` and `end` are offsets in bytes
+fn to_json(error: &Error, content: &str, input_name: &str) -> Value {
+    let kind = match error {
+        Error::Syntax { .. } => "syntax",
+        Error::UnknownNode { .. } => "unknown-node",
+        Error::Duplicate { .. } => "duplicate",
+        Error::InvalidAttribute { .. } => "invalid-attribute",
+        Error::Layout { .. } => "layout",
+        Error::Render { .. } => "render",
+    };
+    let mut value = json!({
+        "file": input_name,
+        "severity": "error",
+        "kind": kind,
+        "message": error.message(),
+        "hint": error.hint(),
+    });
+
+    if let (Some(span), Some(location)) = (error.span(), error.location()) {
+        let (end_line, end_column) = position(content, span.end);
+        value["line"] = json!(location.line);
+        value["column"] = json!(location.column);
+        value["end_line"] = json!(end_line);
+        value["end_column"] = json!(end_column);
+        value["start"] = json!(span.start);
+        value["end"] = json!(span.end);
+    }
+    value
+}
+
+/// The line and column of a byte offset in the content, counted in characters from 1
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = content.get(..offset).unwrap_or(content);
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("graph", 0, (1, 1))]
+    #[case("graph", 5, (1, 6))]
+    #[case("graph\n  a", 6, (2, 1))]
+    #[case("graph\n  a", 9, (2, 4))]
+    #[case("graph\n  a[é]", 12, (2, 6))]
+    #[case("graph\n  a[é]", 13, (2, 7))]
+    #[case("graph\n  a", 100, (2, 4))]
+    fn test_position(
+        #[case] content: &str,
+        #[case] offset: usize,
+        #[case] expected: (usize, usize),
+    ) {
+        assert_eq!(position(content, offset), expected);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let content = "graph\n  a[é]\n  a[Élan]";
+        let errors = graph_core::check(content);
+        let value = to_json(&errors[0], content, "in.graph");
+
+        assert_eq!(value["file"], "in.graph");
+        assert_eq!(value["kind"], "duplicate");
+        assert_eq!(value["line"], 3);
+        assert_eq!(value["column"], 3);
+        assert_eq!(value["end_line"], 3);
+        assert_eq!(value["end_column"], 10);
+        assert_eq!(value["start"], 16);
+        assert_eq!(value["end"], 24);
+    }
+
+    #[rstest]
+    #[case("graph\n  a -> b", "syntax", false)]
+    #[case("graph\n  a --", "syntax", true)]
+    #[case("graph\n  a\n  style b fill:#fff", "unknown-node", true)]
+    fn test_to_json_kind(#[case] content: &str, #[case] kind: &str, #[case] hint: bool) {
+        let errors = graph_core::check(content);
+        let value = to_json(&errors[0], content, "in.graph");
+
+        assert_eq!(value["kind"], kind);
+        assert_eq!(value["hint"].is_string(), hint);
+    }
+
+    #[test]
+    fn test_to_json_without_location() {
+        let error = Error::Layout {
+            message: String::from("failed"),
+        };
+        let value = to_json(&error, "graph", "in.graph");
+
+        assert_eq!(value["kind"], "layout");
+        assert_eq!(value["message"], "failed");
+        assert!(value.get("line").is_none());
+        assert!(value.get("end_column").is_none());
+    }
+}
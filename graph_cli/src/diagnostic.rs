@@ -0,0 +1,105 @@
+use colored::Colorize;
+use graph_core::Error;
+use std::fmt::Write;
+
+/// Formats an error like rustc, with its location, the line of the input it refers to
+/// underlined and a hint for fixing it
+pub(crate) fn format(error: &Error, content: &str, input_name: &str) -> String {
+    let mut diagnostic = format!("{}: {}\n", "error".red().bold(), error.message().bold());
+
+    let (Some(location), Some(span)) = (error.location(), error.span()) else {
+        return diagnostic;
+    };
+    let line_number = location.line.to_string();
+    let gutter = " ".repeat(line_number.len());
+    let bar = "|".blue().bold();
+    let _ = writeln!(
+        diagnostic,
+        "{gutter}{} {input_name}:{}:{}",
+        "-->".blue().bold(),
+        location.line,
+        location.column
+    );
+
+    let line = content.lines().nth(location.line - 1).unwrap_or_default();
+    // Tabs are kept so the carets line up with the line however wide they are shown
+    let indent = line
+        .chars()
+        .take(location.column - 1)
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let length = content
+        .get(span.start..span.end)
+        .map_or(0, |text| text.chars().take_while(|&c| c != '\n').count())
+        .max(1);
+    let _ = writeln!(diagnostic, "{gutter} {bar}");
+    let _ = writeln!(diagnostic, "{} {bar} {line}", line_number.blue().bold());
+    let _ = writeln!(
+        diagnostic,
+        "{gutter} {bar} {indent}{}",
+        "^".repeat(length).red().bold()
+    );
+
+    if let Some(hint) = error.hint() {
+        let _ = writeln!(
+            diagnostic,
+            "{gutter} {} {}: {hint}",
+            "=".blue().bold(),
+            "help".bold()
+        );
+    }
+    diagnostic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn format_first(input: &str) -> String {
+        colored::control::set_override(false);
+        let errors = graph_core::check(input);
+        format(&errors[0], input, "in.graph")
+    }
+
+    #[test]
+    fn test_format() {
+        let expected = "error: unknown node `b`
+ --> in.graph:3:9
+  |
+3 |   style b fill:#fff
+  |         ^
+  = help: there is no node `b` anywhere in the graph, add one such as `b[Label]`
+";
+        assert_eq!(format_first("graph\n  a\n  style b fill:#fff"), expected);
+    }
+
+    #[test]
+    fn test_format_without_location() {
+        colored::control::set_override(false);
+        let error = Error::Layout {
+            message: String::from("failed"),
+        };
+        assert_eq!(format(&error, "", "in.graph"), "error: failed\n");
+    }
+
+    #[rstest]
+    #[case("graph\n\ta -> b", "2 | \ta -> b", "  | \t  ^")]
+    #[case("graph\n  a[é] -> b", "2 |   a[é] -> b", "  |        ^")]
+    #[case("graph\n  a[É]\n  a(B)", "3 |   a(B)", "  |   ^^^^")]
+    #[case("graph\n  a[A]\n  a[Élan]", "3 |   a[Élan]", "  |   ^^^^^^^")]
+    #[case("graph\n  subgraph s {\n    c\n", "4 | ", "  | ^")]
+    fn test_format_carets(#[case] input: &str, #[case] line: &str, #[case] carets: &str) {
+        let diagnostic = format_first(input);
+        let lines = diagnostic.lines().collect::<Vec<_>>();
+        assert_eq!(lines[3], line, "{diagnostic}");
+        assert_eq!(lines[4], carets, "{diagnostic}");
+    }
+
+    #[test]
+    fn test_format_gutter() {
+        let input = format!("graph\n{}  a -> b", "  a\n".repeat(10));
+        let diagnostic = format_first(&input);
+        assert!(diagnostic.contains("\n  --> in.graph:12:5\n   |\n12 |   a -> b\n   |     ^\n"));
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::{Context, Result};
+use graph_core::FormatOptions;
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use crate::{batch, diagnostic, read_input};
+
+/// Formats every .graph file found from the inputs in place, or prints the formatted graph if the
+/// input is stdin. With `check` nothing is written and it fails if any file isn't formatted
+pub(crate) fn fmt(inputs: &[PathBuf], check: bool, options: &FormatOptions) -> Result<ExitCode> {
+    let paths = batch::find_all(inputs)?;
+
+    let mut failed = false;
+    for path in paths.iter() {
+        let (content, input_name) = read_input(path.as_deref())?;
+        let formatted = match graph_core::format_with(&content, options) {
+            Ok(formatted) => formatted,
+            Err(error) => {
+                eprintln!("{}", diagnostic::format(&error, &content, &input_name));
+                failed = true;
+                continue;
+            }
+        };
+
+        match (path, check) {
+            (_, true) => {
+                if formatted != content {
+                    eprintln!("`{input_name}` is not formatted");
+                    failed = true;
+                }
+            }
+            (Some(path), false) => {
+                if formatted != content {
+                    fs::write(path, formatted).with_context(|| {
+                        format!("Could not write to file at `{}`", path.display())
+                    })?;
+                }
+            }
+            (None, false) => io::stdout()
+                .write_all(formatted.as_bytes())
+                .with_context(|| "Could not write to stdout")?,
+        }
+    }
+
+    match failed {
+        true => Ok(ExitCode::FAILURE),
+        false => Ok(ExitCode::SUCCESS),
+    }
+}
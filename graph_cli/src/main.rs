@@ -1,80 +1,341 @@
-use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
-use graph_core;
-use std::fs;
-use tempfile::Builder;
+use anyhow::{bail, Context, Result};
+use check::CheckFormat;
+use clap::{error::ErrorKind, ArgGroup, CommandFactory, Parser, Subcommand};
+use graph_core::{Error, Format, FormatOptions, ParseResult, RenderOptions, Theme};
+use std::{
+    fs,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+use tempfile::{Builder, NamedTempFile};
+
+mod batch;
+mod check;
+mod diagnostic;
+mod fmt;
+mod serve;
+mod watch;
 
 /// Search for a pattern in a file and display the lines that contain it.
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 #[command(next_line_help = true)]
+#[command(group(ArgGroup::new("destination").args(["output_path", "open"]).multiple(true)))]
 struct Arguments {
-    /// The path to the .graph file to read
+    /// The path to the .graph file to read, or `-` to read from stdin. Reads from stdin by default
     #[arg(short, long)]
-    input_path: std::path::PathBuf,
-    /// The path to output to
+    input_path: Option<PathBuf>,
+    /// The path to output to, or `-` to write to stdout. Writes to stdout by default unless the
+    /// output is opened in the browser
     #[arg(short, long)]
-    output_path: Option<std::path::PathBuf>,
-    /// The file format of the output file
-    #[arg(value_enum, short, long, default_value_t = OutputFormat::Svg)]
-    format: OutputFormat,
+    output_path: Option<PathBuf>,
+    /// The .graph files, directories containing them or glob patterns such as
+    /// `"docs/**/*.graph"` to render into the output directory
+    #[arg(requires = "out_dir")]
+    inputs: Vec<PathBuf>,
+    /// The directory to render many files into in parallel, keeping their paths relative to the
+    /// directory or pattern they were found with
+    #[arg(long, conflicts_with_all = ["output_path", "watch", "open"])]
+    out_dir: Option<PathBuf>,
+    /// Render files into the output directory even if their output is newer than them
+    #[arg(long, default_value_t = false, requires = "out_dir")]
+    force: bool,
+    /// The file format of the output file: svg, png, jpg, webp or pdf. Inferred from the extension
+    /// of the output path by default
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<Format>,
     /// Open the output file in the default browser
-    #[arg(short = 'b', long, default_value_t = false)]
+    #[arg(short = 'b', long, default_value_t = false, global = true)]
     open: bool,
+    /// The built-in theme to draw the graph with, overriding the theme selected by the graph
+    #[arg(short, long, value_parser = parse_theme, global = true)]
+    theme: Option<Theme>,
+    /// The built-in theme to switch to when the output is viewed with a dark color scheme
+    #[arg(long, value_parser = parse_theme, global = true)]
+    dark_theme: Option<Theme>,
+    /// The number of pixels per unit of the graph in raster images
+    #[arg(long, default_value_t = 1.0, conflicts_with = "dpi")]
+    scale: f32,
+    /// The resolution of raster images, 96 dpi draws the graph at its actual size
+    #[arg(long)]
+    dpi: Option<f32>,
+    /// The color drawn behind the graph, such as `white` or `#1e1d20`
+    #[arg(long, value_parser = parse_color, global = true)]
+    background: Option<String>,
+    /// The quality of JPG images from 1 to 100
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+    /// Render the graph again every time the input file changes, until interrupted. Needs an
+    /// output file or --open
+    #[arg(short, long, default_value_t = false, requires = "destination")]
+    watch: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-// TODO: Move this into core?
-#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum OutputFormat {
-    Svg,
-    Png,
-    Jpg,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check graphs for errors without rendering them
+    Check {
+        /// The .graph files, directories containing them or glob patterns to check, or `-` to
+        /// check stdin
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// How to print the errors
+        #[arg(short, long, value_enum, default_value_t = CheckFormat::Human)]
+        format: CheckFormat,
+    },
+    /// Format graphs in place, or print the formatted graph when reading from stdin
+    Fmt {
+        /// The .graph files, directories containing them or glob patterns to format, or `-` to
+        /// format stdin
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Don't write the files, fail if any of them aren't formatted
+        #[arg(long, default_value_t = false)]
+        check: bool,
+        /// Move node statements to the start of each block and sort them by id
+        #[arg(long, default_value_t = false)]
+        sort_nodes: bool,
+    },
+    /// Host a page on localhost that shows the graph and reloads it whenever the file changes
+    Serve {
+        /// The path to the .graph file to preview
+        input_path: PathBuf,
+        /// The port to listen on
+        #[arg(short, long, default_value_t = 3000)]
+        port: u16,
+    },
 }
 
-fn main() -> Result<()> {
-    let args = Arguments::parse();
+fn parse_theme(name: &str) -> Result<Theme, String> {
+    Theme::named(name).ok_or_else(|| {
+        format!(
+            "unknown theme `{name}`, expected one of {}",
+            Theme::NAMES.join(", ")
+        )
+    })
+}
+
+fn parse_format(name: &str) -> Result<Format, String> {
+    Format::from_extension(name).ok_or_else(|| {
+        let names = Format::ALL.map(|format| format.extension());
+        format!(
+            "unknown format `{name}`, expected one of {}",
+            names.join(", ")
+        )
+    })
+}
+
+/// Checks a color is valid CSS, such as `white`, `#fff` or `rgba(0, 0, 0, 0.5)`
+fn parse_color(color: &str) -> Result<String, String> {
+    match color.parse::<svgtypes::Color>() {
+        Ok(_) => Ok(String::from(color)),
+        Err(error) => Err(format!("`{color}` is not a valid color: {error}")),
+    }
+}
+
+/// The format with the extension of the path
+fn format_from_path(path: &Path) -> Option<Format> {
+    Format::from_extension(path.extension()?.to_str()?)
+}
+
+/// The path, unless it is missing or `-` for the standard stream
+fn file_path(path: &Option<PathBuf>) -> Option<&Path> {
+    path.as_deref().filter(|path| *path != Path::new("-"))
+}
 
-    let content = fs::read_to_string(&args.input_path)
-        .with_context(|| format!("could not read file `{}`", args.input_path.display()))?;
-
-    let test = graph_core::generate_graph(&content);
-    match test {
-        Ok(graph) => {
-            let clone = graph.clone();
-            if let Some(output_path) = &args.output_path {
-                fs::write(output_path, clone).with_context(|| {
-                    format!(
-                        "Could not write to output file at `{}`",
-                        output_path.display()
-                    )
-                })?;
-            } else {
-                // TODO: Print the graph to the console (with formatting?)
-                // println!("{}", clone);
-            }
-            // Open the output file in the browser
-            if args.open {
-                // Create a temporary file
-                let temp_file = Builder::new()
-                    .suffix(".svg")
-                    .tempfile()
-                    .with_context(|| "Could not create temporary file")?;
-                // Write the graph to the temporary file
-                fs::write(temp_file.path(), graph)?;
-                // Open the temporary file in the browser
-                let output_path = temp_file
-                    .path()
-                    .to_str()
-                    .with_context(|| "Could not convert path to string")?;
-                // Open the file in the browser
-                let _ = webbrowser::open(output_path)
-                    .with_context(|| "Could not open graph with browser")?;
-                // wait for 1s to allow the browser to open
-                std::thread::sleep(std::time::Duration::from_secs(1));
-            }
+/// Reads the input file, or stdin if there is no input path, and returns it with the name to
+/// show in errors
+fn read_input(input_path: Option<&Path>) -> Result<(String, String)> {
+    match input_path {
+        Some(path) => {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("could not read file `{}`", path.display()))?;
+            Ok((content, path.display().to_string()))
         }
-        Err(error) => eprintln!("{error}"),
+        None => {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .with_context(|| "could not read from stdin")?;
+            Ok((content, String::from("<stdin>")))
+        }
+    }
+}
+
+/// Parses and renders a graph, printing the errors in it. Returns the errors if it can't be
+/// rendered
+fn render(
+    content: &str,
+    input_name: &str,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<Vec<u8>, Vec<Error>> {
+    let ParseResult { graph, errors } = graph_core::parse_with_diagnostics(content);
+    let result = match graph {
+        Some(graph) if errors.is_empty() => {
+            graph_core::render(&graph, format, options).map_err(|error| vec![error])
+        }
+        _ => Err(errors),
+    };
+
+    if let Err(errors) = &result {
+        // Printed at once so the errors of files rendered in parallel aren't interleaved
+        let diagnostics = errors
+            .iter()
+            .map(|error| diagnostic::format(error, content, input_name))
+            .collect::<Vec<_>>();
+        eprint!("{}", diagnostics.join("\n"));
     }
+    result
+}
+
+fn write_file(output_path: &Path, output: &[u8]) -> Result<()> {
+    fs::write(output_path, output).with_context(|| {
+        format!(
+            "Could not write to output file at `{}`",
+            output_path.display()
+        )
+    })
+}
 
+/// Writes the output to the output file, or to stdout if there is no output path
+fn write_output(output: &[u8], args: &Arguments, format: Format) -> Result<()> {
+    if let Some(output_path) = file_path(&args.output_path) {
+        write_file(output_path, output)?;
+    } else if args.output_path.is_some() || !args.open {
+        let mut stdout = io::stdout().lock();
+        if format != Format::Svg && stdout.is_terminal() {
+            bail!(
+                "Refusing to write a {} image to the terminal, redirect stdout or use --output-path",
+                format.extension()
+            );
+        }
+        write_stdout(&mut stdout, output)?;
+    }
     Ok(())
 }
+
+/// Writes the output to stdout, stopping quietly if the reader has gone away
+fn write_stdout(stdout: &mut impl Write, output: &[u8]) -> Result<()> {
+    match stdout.write_all(output).and_then(|_| stdout.flush()) {
+        // The reader stopped early, such as `head` in a pipeline
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.with_context(|| "Could not write to stdout"),
+    }
+}
+
+/// Writes the output to a temporary file and opens it in the default browser. The file is
+/// deleted when the returned handle is dropped
+fn open_in_browser(output: &[u8], format: Format) -> Result<NamedTempFile> {
+    // Create a temporary file
+    let temp_file = Builder::new()
+        .suffix(&format!(".{}", format.extension()))
+        .tempfile()
+        .with_context(|| "Could not create temporary file")?;
+    // Write the graph to the temporary file
+    fs::write(temp_file.path(), output)?;
+    // Open the temporary file in the browser
+    let output_path = temp_file
+        .path()
+        .to_str()
+        .with_context(|| "Could not convert path to string")?;
+    // Open the file in the browser
+    webbrowser::open(output_path).with_context(|| "Could not open graph with browser")?;
+    // wait for 1s to allow the browser to open
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    Ok(temp_file)
+}
+
+fn main() -> Result<ExitCode> {
+    let args = Arguments::parse();
+    // Errors are only colored when they are shown in a terminal
+    if !io::stderr().is_terminal() && std::env::var_os("CLICOLOR_FORCE").is_none() {
+        colored::control::set_override(false);
+    }
+
+    let format = match (args.format, file_path(&args.output_path)) {
+        (Some(format), _) => format,
+        (None, Some(output_path)) => match format_from_path(output_path) {
+            Some(format) => format,
+            None => bail!(
+                "Could not infer the format of `{}` from its extension, use --format to set it",
+                output_path.display()
+            ),
+        },
+        (None, None) => Format::Svg,
+    };
+    let options = RenderOptions {
+        theme: args.theme.clone(),
+        dark_theme: args.dark_theme.clone(),
+        background: args.background.clone(),
+        scale: args.dpi.map_or(args.scale, |dpi| dpi / 96.0),
+        quality: args.quality,
+        ..RenderOptions::default()
+    };
+
+    match args.command {
+        Some(Command::Check { inputs, format }) => return check::check(&inputs, format),
+        Some(Command::Fmt {
+            inputs,
+            check,
+            sort_nodes,
+        }) => {
+            let options = FormatOptions {
+                sort_nodes,
+                ..FormatOptions::default()
+            };
+            return fmt::fmt(&inputs, check, &options);
+        }
+        Some(Command::Serve { input_path, port }) => {
+            serve::serve(input_path, port, options, args.open)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => {}
+    }
+
+    if let Some(out_dir) = &args.out_dir {
+        let mut inputs = args.inputs.clone();
+        match (&args.input_path, file_path(&args.input_path)) {
+            (Some(_), None) => bail!("--out-dir needs input files, it can't render stdin"),
+            (_, Some(input_path)) => inputs.push(input_path.to_path_buf()),
+            (None, None) => {}
+        }
+        return batch::render_all(&inputs, out_dir, &args, format, &options);
+    }
+
+    if args.watch {
+        let Some(input_path) = file_path(&args.input_path) else {
+            bail!("--watch needs an input file, it can't watch stdin");
+        };
+        if args.output_path.is_some() && file_path(&args.output_path).is_none() {
+            bail!("--watch needs an output file, it can't write to stdout on every change");
+        }
+        watch::watch(input_path, &args, format, &options)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Waiting for a graph to be typed in is more likely a mistake than intended, `-` still
+    // reads from the terminal
+    if args.input_path.is_none() && io::stdin().is_terminal() {
+        Arguments::command()
+            .error(
+                ErrorKind::MissingRequiredArgument,
+                "no input was given, use --input-path or pipe a graph to stdin",
+            )
+            .exit();
+    }
+    let (content, input_name) = read_input(file_path(&args.input_path))?;
+    let Ok(output) = render(&content, &input_name, format, &options) else {
+        return Ok(ExitCode::FAILURE);
+    };
+    write_output(&output, &args, format)?;
+    // Open the output file in the browser
+    if args.open {
+        open_in_browser(&output, format)?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
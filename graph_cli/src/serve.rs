@@ -0,0 +1,122 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html,
+    },
+    routing::get,
+    Router,
+};
+use graph_core::{Format, RenderOptions};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    thread,
+};
+use tokio::sync::watch;
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+
+use crate::read_input;
+
+/// The page that shows the graph and listens for updates to it
+const PAGE: &str = include_str!("preview.html");
+
+/// The latest state of the graph being previewed
+#[derive(Debug, Clone)]
+enum Preview {
+    Rendered(String),
+    /// The errors in the graph, one per line
+    Failed(String),
+}
+
+/// Hosts a page on localhost that shows the graph and updates whenever the file changes, until
+/// the process is interrupted
+pub(crate) fn serve(
+    input_path: PathBuf,
+    port: u16,
+    options: RenderOptions,
+    open: bool,
+) -> Result<()> {
+    let preview = render(&input_path, &options);
+    let (sender, receiver) = watch::channel(preview);
+
+    let watched_path = input_path.clone();
+    thread::spawn(move || {
+        let watched = crate::watch::on_change(&watched_path, || {
+            sender.send_replace(render(&watched_path, &options));
+        });
+        if let Err(error) = watched {
+            eprintln!("error: {error:#}");
+        }
+    });
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .with_context(|| "Could not start the server")?;
+    runtime.block_on(async move {
+        let app = Router::new()
+            .route("/", get(|| async { Html(PAGE) }))
+            .route("/events", get(events))
+            .with_state(receiver);
+
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], port)))
+            .await
+            .with_context(|| format!("Could not listen on port {port}"))?;
+        let url = format!("http://{}", listener.local_addr()?);
+        eprintln!("serving `{}` at {url}", input_path.display());
+        if open {
+            webbrowser::open(&url).with_context(|| "Could not open the preview with browser")?;
+        }
+
+        axum::serve(listener, app)
+            .await
+            .with_context(|| "The server stopped")
+    })
+}
+
+/// Reads and renders the graph as an SVG document, printing any errors
+fn render(input_path: &Path, options: &RenderOptions) -> Preview {
+    let (content, input_name) = match read_input(Some(input_path)) {
+        Ok(input) => input,
+        Err(error) => {
+            let message = format!("{error:#}");
+            eprintln!("error: {message}");
+            return Preview::Failed(message);
+        }
+    };
+
+    match crate::render(&content, &input_name, Format::Svg, options) {
+        Ok(document) => {
+            eprintln!("rendered `{input_name}`");
+            Preview::Rendered(String::from_utf8_lossy(&document).into_owned())
+        }
+        Err(errors) => Preview::Failed(
+            errors
+                .iter()
+                .map(|error| match error.location() {
+                    Some(_) => format!("{input_name}:{error}"),
+                    None => error.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+    }
+}
+
+/// Sends the current state of the graph and then every update to it as server-sent events
+async fn events(
+    State(receiver): State<watch::Receiver<Preview>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    // Lines of the data are split into separate fields, but carriage returns can't be sent
+    let stream = WatchStream::new(receiver).map(|preview| {
+        let (event, data) = match preview {
+            Preview::Rendered(document) => ("render", document),
+            Preview::Failed(errors) => ("errors", errors),
+        };
+        Ok(Event::default().event(event).data(data.replace('\r', "")))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,128 @@
+use anyhow::{Context, Result};
+use graph_core::{Format, RenderOptions};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{fs, path::Path, sync::mpsc, time::Duration};
+use tempfile::NamedTempFile;
+
+use crate::{file_path, open_in_browser, read_input, render, write_file, Arguments};
+
+/// How long to wait for more events after a change, as editors often save a file in several steps
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Renders the graph every time the input file changes until the process is interrupted. Errors
+/// are printed instead of stopping the watch
+pub(crate) fn watch(
+    input_path: &Path,
+    args: &Arguments,
+    format: Format,
+    options: &RenderOptions,
+) -> Result<()> {
+    // The browser is only opened once, after that the page is refreshed to see changes
+    let mut preview: Option<NamedTempFile> = None;
+    let mut changed = || {
+        match update(input_path, args, format, options, &mut preview) {
+            Ok(true) => eprintln!("rendered `{}`", input_path.display()),
+            Ok(false) => {}
+            Err(error) => eprintln!("error: {error:#}"),
+        }
+        eprintln!("watching `{}` for changes...", input_path.display());
+    };
+    changed();
+    on_change(input_path, changed)
+}
+
+/// Calls the function every time the file at the path changes, until watching fails
+pub(crate) fn on_change(path: &Path, mut changed: impl FnMut()) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .with_context(|| "Could not start watching for changes")?;
+    // Editors often save by replacing the file, which ends a watch on the file itself, so the
+    // directory containing it is watched instead
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Could not watch `{}`", directory.display()))?;
+
+    loop {
+        // Wait for a change to the file, then for the rest of the events from saving it
+        loop {
+            let event = receiver
+                .recv()
+                .with_context(|| "Stopped watching for changes")?;
+            if is_change(&event, path) {
+                break;
+            }
+        }
+        while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+        changed();
+    }
+}
+
+/// Renders the input file and writes the output, returning whether the graph had no errors
+fn update(
+    input_path: &Path,
+    args: &Arguments,
+    format: Format,
+    options: &RenderOptions,
+    preview: &mut Option<NamedTempFile>,
+) -> Result<bool> {
+    let (content, input_name) = read_input(Some(input_path))?;
+    let Ok(output) = render(&content, &input_name, format, options) else {
+        return Ok(false);
+    };
+
+    if let Some(output_path) = file_path(&args.output_path) {
+        write_file(output_path, &output)?;
+    }
+    if args.open {
+        match preview {
+            Some(file) => fs::write(file.path(), &output)?,
+            None => *preview = Some(open_in_browser(&output, format)?),
+        }
+    }
+    Ok(true)
+}
+
+/// Whether the event changes the contents of the file at the path
+fn is_change(event: &notify::Result<notify::Event>, path: &Path) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Any
+    ) && event
+        .paths
+        .iter()
+        .any(|changed| changed.file_name() == path.file_name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, ModifyKind, RemoveKind};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(EventKind::Modify(ModifyKind::Any), "dir/a.graph", true)]
+    #[case(EventKind::Create(CreateKind::File), "dir/a.graph", true)]
+    #[case(EventKind::Any, "dir/a.graph", true)]
+    #[case(EventKind::Modify(ModifyKind::Any), "dir/b.graph", false)]
+    #[case(EventKind::Modify(ModifyKind::Any), "dir/a.graph.swp", false)]
+    #[case(EventKind::Remove(RemoveKind::File), "dir/a.graph", false)]
+    #[case(EventKind::Access(AccessKind::Any), "dir/a.graph", false)]
+    fn test_is_change(#[case] kind: EventKind, #[case] changed: &str, #[case] expected: bool) {
+        let event = notify::Event::new(kind).add_path(changed.into());
+        assert_eq!(is_change(&Ok(event), Path::new("dir/a.graph")), expected);
+    }
+
+    #[test]
+    fn test_is_change_error() {
+        let event = Err(notify::Error::generic("watch failed"));
+        assert!(!is_change(&event, Path::new("dir/a.graph")));
+    }
+}
@@ -0,0 +1,174 @@
+use pest::iterators::Pair;
+use svg::node::element::{Group, Rectangle, Text};
+
+use crate::{
+    options::{LayoutOptions, RenderOptions},
+    parser::Rule,
+    render::{measure_text_width, ToSvg},
+};
+
+/// A named group of nodes drawn as a labeled container, declared with `subgraph id[Label] { ... }`
+#[derive(Debug, PartialEq, Clone)]
+pub struct Cluster {
+    pub id: String,
+    pub label: Option<String>,
+    /// The ids of the nodes declared or referenced inside the cluster
+    pub nodes: Vec<String>,
+    pub(crate) position: Option<(f32, f32)>,
+    pub(crate) size: Option<(f32, f32)>,
+}
+
+impl Cluster {
+    /// An empty cluster without a label, which is titled with its id
+    pub fn new(id: impl Into<String>) -> Cluster {
+        Cluster {
+            id: id.into(),
+            label: None,
+            nodes: Vec::new(),
+            position: None,
+            size: None,
+        }
+    }
+
+    /// The top left corner of the cluster, once the graph is laid out
+    pub fn position(&self) -> Option<(f32, f32)> {
+        self.position
+    }
+
+    /// The size of the cluster, once the graph is laid out
+    pub fn size(&self) -> Option<(f32, f32)> {
+        self.size
+    }
+
+    fn title(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.id)
+    }
+
+    /// The height of the space above the members of the cluster reserved for the label
+    pub(crate) fn header_height(&self, options: &LayoutOptions) -> f32 {
+        let (_, text_height) = self.measure_title(options);
+        text_height + options.cluster_padding * 1.5
+    }
+
+    fn measure_title(&self, options: &LayoutOptions) -> (f32, f32) {
+        measure_text_width(
+            self.title(),
+            options.cluster_label_font_size,
+            &options.font_family,
+        )
+    }
+
+    /// Sizes and positions the cluster around the bounds of its members in the format
+    /// (min_x, min_y, max_x, max_y)
+    pub(crate) fn fit(&mut self, bounds: (f32, f32, f32, f32), options: &LayoutOptions) {
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let (label_width, _) = self.measure_title(options);
+        let header = self.header_height(options);
+        let padding = options.cluster_padding;
+
+        let width = f32::max(max_x - min_x, label_width) + padding * 2.0;
+        let height = max_y - min_y + header + padding;
+        let center_x = (min_x + max_x) / 2.0;
+
+        self.position = Some((center_x - width / 2.0, min_y - header));
+        self.size = Some((width, height));
+    }
+}
+
+impl ToSvg<Group> for Cluster {
+    fn to_svg(&mut self, options: &RenderOptions) -> Group {
+        let (x, y) = self.position.unwrap_or((0.0, 0.0));
+        let (width, height) = self.size.unwrap_or((0.0, 0.0));
+        let (_, label_height) = self.measure_title(&options.layout);
+        let padding = options.layout.cluster_padding;
+
+        Group::new()
+            .set("id", format!("cluster_{}", self.id))
+            .set("class", "cluster")
+            .set("transform", format!("translate({}, {})", x, y))
+            .add(
+                Rectangle::new()
+                    .set("width", width)
+                    .set("height", height)
+                    .set("rx", 8.0)
+                    .set("class", "cluster-shape")
+                    .set("fill", options.theme().cluster_fill.as_str())
+                    .set("stroke", options.theme().cluster_stroke.as_str())
+                    .set("stroke-width", 1),
+            )
+            .add(
+                Text::new(self.title())
+                    .set(
+                        "font-size",
+                        format!("{}px", options.layout.cluster_label_font_size),
+                    )
+                    .set("x", padding)
+                    .set("y", padding / 2.0 + label_height),
+            )
+    }
+}
+
+impl<'a> From<Pair<'a, Rule>> for Cluster {
+    fn from(pair: Pair<Rule>) -> Cluster {
+        let mut cluster = Cluster::new("");
+
+        for cluster_pair in pair.into_inner() {
+            match cluster_pair.as_rule() {
+                Rule::id => cluster.id = cluster_pair.as_str().to_string(),
+                Rule::subgraph_label => {
+                    let label = cluster_pair.as_str().trim();
+                    if !label.is_empty() {
+                        cluster.label = Some(String::from(label));
+                    }
+                }
+                _ => {}
+            }
+        }
+        cluster
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pest::Parser;
+    use rstest::rstest;
+
+    fn get_subgraph(input: &str) -> Pair<'_, Rule> {
+        use crate::parser::GraphParser;
+
+        let parse_result = GraphParser::parse(Rule::subgraph, input);
+        parse_result.unwrap().next().unwrap()
+    }
+
+    #[rstest]
+    #[case("subgraph a {\n}", "a", None)]
+    #[case("subgraph a[] {\n}", "a", None)]
+    #[case(
+        "subgraph backend[Backend services] {\n  a\n}",
+        "backend",
+        Some("Backend services")
+    )]
+    #[case("subgraph b[B]{\n  a --> b\n  c\n}", "b", Some("B"))]
+    fn test_cluster_from_pair(#[case] input: &str, #[case] id: &str, #[case] label: Option<&str>) {
+        let pair = get_subgraph(input);
+        let cluster = Cluster::from(pair);
+        assert_eq!(cluster.id, id);
+        assert_eq!(cluster.label, label.map(String::from));
+    }
+
+    #[rstest]
+    #[case("subgraph a {}")]
+    #[case("subgraph {\n}")]
+    #[case("subgraph a[A {\n}")]
+    #[case("subgraph a {\n  subgraph b {\n  }\n}")]
+    fn test_cluster_invalid(#[case] input: &str) {
+        use crate::parser::GraphParser;
+
+        // Invalid statements inside a subgraph are skipped to recover from them
+        let parsed = GraphParser::parse(Rule::subgraph, input);
+        assert!(parsed.map_or(true, |pairs| pairs
+            .flatten()
+            .any(|pair| matches!(pair.as_rule(), Rule::invalid | Rule::unclosed))));
+    }
+}
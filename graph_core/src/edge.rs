@@ -1,26 +1,46 @@
 use pest::iterators::Pair;
-use svg::node::element::{Group, Line};
+use std::collections::BTreeMap;
+use svg::node::element::{Circle, Group, Marker, Path, Polygon, Rectangle, Text};
 
-use crate::parser::Rule;
+use crate::{
+    node::Node,
+    options::{LayoutOptions, RenderOptions, Theme},
+    parser::Rule,
+    render::{measure_text_width, with_style},
+    style::Style,
+};
 
+/// A connection between two nodes, declared with `source --> target`
 #[derive(Debug, PartialEq, Clone)]
-
-pub(crate) struct Edge {
+pub struct Edge {
+    /// The id of the node the edge starts at
+    pub source: String,
+    /// The id of the node the edge ends at
+    pub target: String,
     /// The label of the edge
     pub label: Option<String>,
     /// The line style of the edge --, -., ==, ~~
     pub line: EdgeLine,
-    /// The source head of the edge <--, --|, --, --:
+    /// The source head of the edge <--, |--, :--, }--
     pub source_head: EdgeHead,
-    /// The target head of the edge -->, --|, --, --:
+    /// The target head of the edge -->, --|, --:, --{
     pub target_head: EdgeHead,
-    /// The position of the edge label in the format (x1, y1, x2, y2)
-    pub position: Option<(f32, f32, f32, f32)>,
+    /// Colors and line styles that override the theme for this edge, the fill is used for the
+    /// background of the label
+    pub style: Style,
+    /// The start and end of the edge in the format (x1, y1, x2, y2)
+    pub(crate) position: Option<(f32, f32, f32, f32)>,
+    /// The control points of the edge when it is drawn as a cubic bezier curve
+    pub(crate) curve: Option<((f32, f32), (f32, f32))>,
+    /// The center of the label of the edge
+    pub(crate) label_position: Option<(f32, f32)>,
+    /// The size of the label of the edge including its background, measured when the graph is
+    /// laid out
+    pub(crate) label_size: Option<(f32, f32)>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-
-pub(crate) enum EdgeLine {
+pub enum EdgeLine {
     Thin,
     Dotted,
     Thick,
@@ -28,12 +48,12 @@ pub(crate) enum EdgeLine {
 }
 
 #[derive(Debug, PartialEq, Clone)]
-
-pub(crate) enum EdgeHead {
+pub enum EdgeHead {
     Left,
     Right,
     Straight,
     Dot,
+    CrowsFoot,
     None,
 }
 
@@ -44,6 +64,7 @@ impl From<&str> for EdgeHead {
             ">" => EdgeHead::Right,
             "|" => EdgeHead::Straight,
             ":" => EdgeHead::Dot,
+            "{" | "}" => EdgeHead::CrowsFoot,
             _ => EdgeHead::None,
         }
     }
@@ -55,57 +76,367 @@ impl From<&str> for EdgeLine {
             "-." => EdgeLine::Dotted,
             "~~" => EdgeLine::Wavy,
             "==" => EdgeLine::Thick,
-            "--" | _ => EdgeLine::Thin,
+            _ => EdgeLine::Thin,
+        }
+    }
+}
+
+impl EdgeLine {
+    /// The width of the stroke, markers are scaled by it
+    pub(crate) fn stroke_width(&self) -> f32 {
+        match self {
+            EdgeLine::Thick => 2.0,
+            _ => 1.0,
+        }
+    }
+
+    fn dash_array(&self) -> Option<&'static str> {
+        match self {
+            EdgeLine::Dotted => Some("1 3"),
+            _ => None,
         }
     }
 }
 
 impl EdgeHead {
-    fn into_id(&self) -> &'static str {
+    /// The id of the marker drawn for the head. Markers are drawn pointing along the edge into
+    /// the target and are reversed at the start of the edge
+    fn marker_id(&self) -> Option<&'static str> {
+        match self {
+            EdgeHead::Left | EdgeHead::Right => Some("arrow"),
+            EdgeHead::Straight => Some("bar"),
+            EdgeHead::Dot => Some("dot"),
+            EdgeHead::CrowsFoot => Some("crows-foot"),
+            EdgeHead::None => None,
+        }
+    }
+
+    /// How far the marker of the head extends past the end of the edge, before being scaled by
+    /// the width of the line
+    pub(crate) fn length(&self) -> f32 {
         match self {
-            EdgeHead::Left => "url(#left-arrow)",
-            EdgeHead::Right => "url(#right-arrow)",
-            EdgeHead::Straight => "",
-            EdgeHead::Dot => "",
-            _ => "",
+            EdgeHead::Left | EdgeHead::Right => 5.0,
+            EdgeHead::Straight => 1.5,
+            EdgeHead::Dot => 4.0,
+            EdgeHead::CrowsFoot => 8.0,
+            EdgeHead::None => 0.0,
+        }
+    }
+
+    /// The definitions of the markers used by the heads of edges
+    pub(crate) fn markers(theme: &Theme) -> Vec<Marker> {
+        [
+            EdgeHead::Right,
+            EdgeHead::Straight,
+            EdgeHead::Dot,
+            EdgeHead::CrowsFoot,
+        ]
+        .iter()
+        .filter_map(|head| head.marker(&theme.edge_stroke, None))
+        .collect()
+    }
+
+    /// The definitions of the markers used by the heads of edges with their own stroke color,
+    /// only for the heads that are drawn in each color
+    pub(crate) fn styled_markers(edges: &[Edge]) -> Vec<Marker> {
+        let mut used = BTreeMap::new();
+        for edge in edges {
+            let Some(color) = edge.style.stroke.as_deref() else {
+                continue;
+            };
+            for head in [&edge.source_head, &edge.target_head] {
+                if let Some(id) = head.marker_id() {
+                    used.insert((color, id), head);
+                }
+            }
+        }
+        used.into_iter()
+            .filter_map(|((color, _), head)| head.marker(color, Some(color)))
+            .collect()
+    }
+
+    /// The id of a marker, the markers of edges with their own stroke color have the color
+    /// appended so each color has its own markers. Characters that can't be in an id are
+    /// replaced by their code point so different colors never share an id
+    fn styled_marker_id(id: &str, color: Option<&str>) -> String {
+        let Some(color) = color else {
+            return String::from(id);
+        };
+        let mut styled_id = format!("{id}-");
+        for c in color.chars() {
+            match c.is_ascii_alphanumeric() {
+                true => styled_id.push(c),
+                false => styled_id.push_str(&format!("_{:x}_", c as u32)),
+            }
         }
+        styled_id
+    }
+
+    /// The definition of the marker drawn for the head in the color, if it has one
+    fn marker(&self, color: &str, styled: Option<&str>) -> Option<Marker> {
+        let id = self.marker_id()?;
+        // Styled markers set their color inline so it isn't replaced by the dark theme
+        let style = Style {
+            fill: styled.map(String::from),
+            stroke: styled.map(String::from),
+            ..Style::default()
+        };
+        let height = match self {
+            EdgeHead::Left | EdgeHead::Right => 5.0,
+            EdgeHead::Dot => 4.0,
+            _ => 8.0,
+        };
+        let marker = Marker::new()
+            .set("id", EdgeHead::styled_marker_id(id, styled))
+            .set("markerWidth", self.length())
+            .set("markerHeight", height)
+            .set("refX", 0)
+            .set("refY", height / 2.0)
+            .set("orient", "auto-start-reverse")
+            .set("class", "edge-head")
+            .set("fill", color);
+        let marker = with_style(marker, &style, &["fill"]);
+
+        Some(match self {
+            EdgeHead::Left | EdgeHead::Right => {
+                marker.add(Polygon::new().set("points", "0 0, 5 2.5, 0 5"))
+            }
+            EdgeHead::Straight => marker.add(Rectangle::new().set("width", 1.5).set("height", 8)),
+            EdgeHead::Dot => marker.add(Circle::new().set("cx", 2).set("cy", 2).set("r", 2)),
+            EdgeHead::CrowsFoot => marker.add(with_style(
+                Path::new()
+                    .set("d", "M 0 4 L 8 0 M 0 4 L 8 4 M 0 4 L 8 8")
+                    .set("class", "edge-head-line")
+                    .set("fill", "none")
+                    .set("stroke", color)
+                    .set("stroke-width", 1),
+                &style,
+                &["stroke"],
+            )),
+            EdgeHead::None => return None,
+        })
     }
 }
 
 impl Edge {
-    fn new() -> Self {
+    pub fn new(source: impl Into<String>, target: impl Into<String>) -> Self {
         Self {
+            source: source.into(),
+            target: target.into(),
             label: None,
             line: EdgeLine::Thin,
             source_head: EdgeHead::None,
             target_head: EdgeHead::None,
+            style: Style::default(),
             position: None,
+            curve: None,
+            label_position: None,
+            label_size: None,
         }
     }
 
-    pub fn to_svg(&self) -> Group {
-        let position = match self.position {
-            Some(position) => position,
-            None => (0.0, 0.0, 0.0, 0.0),
-        };
+    /// The start and end of the edge in the format (x1, y1, x2, y2), once the graph is laid out
+    pub fn position(&self) -> Option<(f32, f32, f32, f32)> {
+        self.position
+    }
+
+    /// The center of the label of the edge, once the graph is laid out
+    pub fn label_position(&self) -> Option<(f32, f32)> {
+        self.label_position
+    }
 
-        let mut group = Group::new().add(
-            Line::new()
-                .set("x1", position.0)
-                .set("y1", position.1)
-                .set("x2", position.2)
-                .set("y2", position.3)
-                .set("stroke", "#5d5b5d")
-                .set("stroke-width", 1),
-        );
+    /// Measures the label of the edge including its background
+    pub(crate) fn measure_label(&mut self, options: &LayoutOptions) {
+        self.label_size = self.label.as_ref().map(|label| {
+            let (width, height) =
+                measure_text_width(label, options.edge_label_font_size, &options.font_family);
+            let padding = options.edge_label_padding;
+            (width + padding.0 * 2.0, height + padding.1 * 2.0)
+        });
+    }
 
-        if self.source_head != EdgeHead::None {
-            let head = self.source_head.into_id();
-            group = group.set("marker-end", head);
+    /// The size of the label of the edge including its background, or nothing if it has no label
+    /// or it hasn't been measured
+    pub(crate) fn label_size(&self) -> (f32, f32) {
+        self.label_size.unwrap_or((0.0, 0.0))
+    }
+
+    /// The bounds of the label in the format (min_x, min_y, max_x, max_y)
+    pub(crate) fn label_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        let (x, y) = self.label_position?;
+        let (width, height) = self.label_size();
+        Some((
+            x - width / 2.0,
+            y - height / 2.0,
+            x + width / 2.0,
+            y + height / 2.0,
+        ))
+    }
+
+    /// The start, end and control points of the edge
+    pub(crate) fn points(&self) -> Vec<(f32, f32)> {
+        let mut points = Vec::new();
+        if let Some((x1, y1, x2, y2)) = self.position {
+            points.push((x1, y1));
+            points.push((x2, y2));
+        }
+        if let Some((c1, c2)) = self.curve {
+            points.push(c1);
+            points.push(c2);
         }
-        if self.target_head != EdgeHead::None {
-            let head = self.target_head.into_id();
-            group = group.set("marker-end", head);
+        if let Some((min_x, min_y, max_x, max_y)) = self.label_bounds() {
+            points.push((min_x, min_y));
+            points.push((max_x, max_y));
+        }
+        points
+    }
+
+    /// Moves the edge by `(dx, dy)`
+    pub(crate) fn translate(&mut self, dx: f32, dy: f32) {
+        if let Some((x1, y1, x2, y2)) = self.position {
+            self.position = Some((x1 + dx, y1 + dy, x2 + dx, y2 + dy));
+        }
+        if let Some(((c1x, c1y), (c2x, c2y))) = self.curve {
+            self.curve = Some(((c1x + dx, c1y + dy), (c2x + dx, c2y + dy)));
+        }
+        if let Some((x, y)) = self.label_position {
+            self.label_position = Some((x + dx, y + dy));
+        }
+    }
+
+    /// The point at `t` along the edge, where 0 is the start and 1 is the end
+    pub(crate) fn point_at(&self, t: f32) -> (f32, f32) {
+        let (x1, y1, x2, y2) = self.position.unwrap_or((0.0, 0.0, 0.0, 0.0));
+        match self.curve {
+            Some(((c1x, c1y), (c2x, c2y))) => {
+                let u = 1.0 - t;
+                let (a, b, c, d) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+                (
+                    a * x1 + b * c1x + c * c2x + d * x2,
+                    a * y1 + b * c1y + c * c2y + d * y2,
+                )
+            }
+            None => (x1 + (x2 - x1) * t, y1 + (y2 - y1) * t),
+        }
+    }
+
+    /// The SVG path data of the edge
+    fn path_data(&self) -> String {
+        let (x1, y1, x2, y2) = self.position.unwrap_or((0.0, 0.0, 0.0, 0.0));
+        match (&self.line, self.curve) {
+            (EdgeLine::Wavy, _) => self.wavy_path_data(),
+            (_, Some(((c1x, c1y), (c2x, c2y)))) => {
+                format!("M {x1} {y1} C {c1x} {c1y}, {c2x} {c2y}, {x2} {y2}")
+            }
+            (_, None) => format!("M {x1} {y1} L {x2} {y2}"),
+        }
+    }
+
+    /// The SVG path data of a sine wave following the edge. The wave fades out towards the ends
+    /// so the edge still meets its markers head on
+    fn wavy_path_data(&self) -> String {
+        let (amplitude, wavelength) = (2.0, 8.0);
+
+        // Sample the edge roughly every pixel
+        let length = (0..16)
+            .map(|step| {
+                let (ax, ay) = self.point_at(step as f32 / 16.0);
+                let (bx, by) = self.point_at((step + 1) as f32 / 16.0);
+                ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt()
+            })
+            .sum::<f32>();
+        let steps = usize::max(2, length.ceil() as usize);
+        let points = (0..=steps)
+            .map(|step| self.point_at(step as f32 / steps as f32))
+            .collect::<Vec<_>>();
+
+        let mut data = String::new();
+        let mut distance = 0.0;
+        for (index, (x, y)) in points.iter().enumerate() {
+            let (px, py) = points[index.saturating_sub(1)];
+            let (nx, ny) = points[usize::min(index + 1, steps)];
+            distance += ((x - px).powi(2) + (y - py).powi(2)).sqrt();
+
+            let (tx, ty) = (nx - px, ny - py);
+            let tangent_length = f32::max((tx * tx + ty * ty).sqrt(), f32::EPSILON);
+            let taper = f32::min(1.0, f32::min(distance, length - distance) / wavelength).max(0.0);
+            let offset = amplitude * taper * (distance / wavelength * std::f32::consts::TAU).sin();
+            let (wx, wy) = (
+                x - ty / tangent_length * offset,
+                y + tx / tangent_length * offset,
+            );
+
+            let command = if index == 0 { "M" } else { " L" };
+            data.push_str(&format!("{command} {wx:.2} {wy:.2}"));
+        }
+        data
+    }
+
+    /// The width of the line of the edge, set by its style or its line
+    pub(crate) fn stroke_width(&self) -> f32 {
+        self.style
+            .stroke_width
+            .unwrap_or_else(|| self.line.stroke_width())
+    }
+
+    pub(crate) fn to_svg(&self, options: &RenderOptions) -> Group {
+        let mut path = Path::new()
+            .set("d", self.path_data())
+            .set("class", "edge-line")
+            .set("fill", "none")
+            .set("stroke", options.theme().edge_stroke.as_str())
+            .set("stroke-width", self.stroke_width());
+        if let Some(dash_array) = self.line.dash_array() {
+            path = path
+                .set("stroke-dasharray", dash_array)
+                .set("stroke-linecap", "round");
+        }
+
+        // Markers are set on the path so they aren't applied to the label
+        let color = self.style.stroke.as_deref();
+        if let Some(id) = self.source_head.marker_id() {
+            let id = EdgeHead::styled_marker_id(id, color);
+            path = path.set("marker-start", format!("url(#{id})"));
+        }
+        if let Some(id) = self.target_head.marker_id() {
+            let id = EdgeHead::styled_marker_id(id, color);
+            path = path.set("marker-end", format!("url(#{id})"));
+        }
+
+        let mut group = Group::new().add(with_style(
+            path,
+            &self.style,
+            &["stroke", "stroke-dasharray"],
+        ));
+
+        if let (Some(label), Some((x, y))) = (&self.label, self.label_position) {
+            let (width, height) = self.label_size();
+            let text_height = height - options.layout.edge_label_padding.1 * 2.0;
+            group = group
+                .add(with_style(
+                    Rectangle::new()
+                        .set("x", x - width / 2.0)
+                        .set("y", y - height / 2.0)
+                        .set("width", width)
+                        .set("height", height)
+                        .set("rx", 2.0)
+                        .set("class", "edge-label")
+                        .set("fill", options.theme().edge_label_fill.as_str()),
+                    &self.style,
+                    &["fill"],
+                ))
+                .add(with_style(
+                    Text::new(label)
+                        .set(
+                            "font-size",
+                            format!("{}px", options.layout.edge_label_font_size),
+                        )
+                        .set("text-anchor", "middle")
+                        .set("x", x)
+                        .set("y", y + text_height / 2.0),
+                    &self.style,
+                    &["color", "font-weight"],
+                ));
         }
 
         group
@@ -114,11 +445,19 @@ impl Edge {
 
 impl<'a> From<Pair<'a, Rule>> for Edge {
     fn from(pair: Pair<Rule>) -> Self {
-        let mut edge = Edge::new();
+        let mut edge = Edge::new("", "");
         let edge_pairs = pair.into_inner();
         for edge_pair in edge_pairs {
             let rule = edge_pair.as_rule();
             match rule {
+                Rule::node => {
+                    let node_id = Node::from(edge_pair.clone()).id;
+                    match edge_pair.as_node_tag() {
+                        Some("source") => edge.source = node_id,
+                        Some("target") => edge.target = node_id,
+                        _ => {}
+                    }
+                }
                 Rule::edge_label => {
                     edge.label = Some(edge_pair.as_str().trim_matches('|').to_string())
                 }
@@ -138,13 +477,11 @@ mod tests {
     use pest::Parser;
     use rstest::*;
 
-    fn get_pair(input: &str) -> Pair<Rule> {
+    fn get_pair(input: &str) -> Pair<'_, Rule> {
         use crate::parser::GraphParser;
 
-        let parse_result = GraphParser::parse(Rule::edge, &input);
-        let pair = parse_result.unwrap().next().unwrap();
-
-        pair
+        let parse_result = GraphParser::parse(Rule::edge, input);
+        parse_result.unwrap().next().unwrap()
     }
 
     #[rstest]
@@ -162,6 +499,109 @@ mod tests {
         assert_eq!(edge.target_head, EdgeHead::Right);
     }
 
+    #[rstest]
+    #[case(EdgeLine::Thin, None)]
+    #[case(EdgeLine::Dotted, Some("stroke-dasharray=\"1 3\""))]
+    #[case(EdgeLine::Thick, Some("stroke-width=\"2\""))]
+    #[case(EdgeLine::Wavy, None)]
+    fn test_edge_line_to_svg(#[case] line: EdgeLine, #[case] attribute: Option<&str>) {
+        let mut edge = Edge::new("a", "b");
+        edge.line = line.clone();
+        edge.position = Some((0.0, 0.0, 0.0, 100.0));
+        let svg = edge.to_svg(&RenderOptions::default()).to_string();
+
+        if let Some(attribute) = attribute {
+            assert!(svg.contains(attribute));
+        }
+        // The wavy line is made up of many short segments, the others are a single one
+        let segments = svg.matches(" L ").count();
+        match line {
+            EdgeLine::Wavy => assert!(segments >= 100),
+            _ => assert_eq!(segments, 1),
+        }
+    }
+
+    #[rstest]
+    #[case("a --> b", None, Some("arrow"))]
+    #[case("a <-- b", Some("arrow"), None)]
+    #[case("a <--> b", Some("arrow"), Some("arrow"))]
+    #[case("a :--: b", Some("dot"), Some("dot"))]
+    #[case("a |--> b", Some("bar"), Some("arrow"))]
+    #[case("a }--{ b", Some("crows-foot"), Some("crows-foot"))]
+    #[case("a -- b", None, None)]
+    fn test_edge_markers_to_svg(
+        #[case] input: &str,
+        #[case] start: Option<&str>,
+        #[case] end: Option<&str>,
+    ) {
+        let mut edge = Edge::from(get_pair(input));
+        edge.position = Some((0.0, 0.0, 0.0, 100.0));
+        let svg = edge.to_svg(&RenderOptions::default()).to_string();
+
+        let marker = |attribute: &str, id: Option<&str>| match id {
+            Some(id) => assert!(svg.contains(&format!("{attribute}=\"url(#{id})\""))),
+            None => assert!(!svg.contains(attribute)),
+        };
+        marker("marker-start", start);
+        marker("marker-end", end);
+    }
+
+    #[test]
+    fn test_styled_marker_id() {
+        let ids = ["rgb(1,23,4)", "rgb(12,3,4)", "#f9f", "f9f"]
+            .map(|color| EdgeHead::styled_marker_id("arrow", Some(color)));
+        assert_eq!(ids[0], "arrow-rgb_28_1_2c_23_2c_4_29_");
+        for (index, id) in ids.iter().enumerate() {
+            assert!(!ids[index + 1..].contains(id));
+        }
+    }
+
+    #[test]
+    fn test_styled_markers() {
+        let edge = |input: &str, color: Option<&str>| {
+            let mut edge = Edge::from(get_pair(input));
+            edge.style.stroke = color.map(String::from);
+            edge
+        };
+        let edges = [
+            edge("a --> b", Some("red")),
+            edge("a <--> c", Some("red")),
+            edge("a :--| b", Some("blue")),
+            edge("a }--{ b", None),
+        ];
+        let ids = EdgeHead::styled_markers(&edges)
+            .iter()
+            .map(|marker| marker.get_attributes()["id"].to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["bar-blue", "dot-blue", "arrow-red"]);
+    }
+
+    #[test]
+    fn test_edge_label_to_svg() {
+        let mut edge = Edge::new("a", "b");
+        edge.label = Some(String::from("Edge Label"));
+        edge.measure_label(&LayoutOptions::default());
+        edge.position = Some((0.0, 0.0, 0.0, 100.0));
+        edge.label_position = edge.position.map(|_| edge.point_at(0.5));
+        let svg = edge.to_svg(&RenderOptions::default()).to_string();
+
+        assert!(svg.contains("Edge Label"));
+        assert!(svg.contains("<rect"));
+        assert!(svg.contains("text-anchor=\"middle\" x=\"0\" y=\"5"));
+    }
+
+    #[test]
+    fn test_edge_wavy_path_meets_ends() {
+        let mut edge = Edge::new("a", "b");
+        edge.line = EdgeLine::Wavy;
+        edge.position = Some((10.0, 20.0, 110.0, 20.0));
+        let data = edge.wavy_path_data();
+
+        assert!(data.starts_with("M 10.00 20.00"));
+        assert!(data.ends_with("L 110.00 20.00"));
+        assert!(data.contains(" 22.00") && data.contains(" 18.00"));
+    }
+
     #[rstest]
     #[case("a --> b", None, EdgeLine::Thin, EdgeHead::None, EdgeHead::Right)]
     #[case("a <--> b", None, EdgeLine::Thin, EdgeHead::Left, EdgeHead::Right)]
@@ -171,6 +611,20 @@ mod tests {
     #[case("a <-. b", None, EdgeLine::Dotted, EdgeHead::Left, EdgeHead::None)]
     #[case("a :--: b", None, EdgeLine::Thin, EdgeHead::Dot, EdgeHead::Dot)]
     #[case("a |--> b", None, EdgeLine::Thin, EdgeHead::Straight, EdgeHead::Right)]
+    #[case(
+        "a }--{ b",
+        None,
+        EdgeLine::Thin,
+        EdgeHead::CrowsFoot,
+        EdgeHead::CrowsFoot
+    )]
+    #[case(
+        "a |--{ b",
+        None,
+        EdgeLine::Thin,
+        EdgeHead::Straight,
+        EdgeHead::CrowsFoot
+    )]
     #[case(
         "a <-- |Edge Label| b",
         Some("Edge Label"),
@@ -0,0 +1,365 @@
+use std::fmt;
+
+use pest::error::{ErrorVariant, InputLocation, LineColLocation};
+
+use crate::parser::Rule;
+
+/// A range of bytes in the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A position in the input, lines and columns are counted from 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The kind of mistake a syntax error is, used to suggest how to fix it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    /// The input does not match what the grammar expects at the location
+    Unexpected,
+    /// An edge has no node after its arrow or label
+    MissingTarget,
+    /// A subgraph is not closed before the end of the input
+    UnclosedSubgraph,
+    /// A subgraph is declared inside another subgraph
+    NestedSubgraph,
+}
+
+/// An error produced while parsing, laying out or rendering a graph
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The input does not match the graph language
+    Syntax {
+        kind: SyntaxKind,
+        message: String,
+        span: Span,
+        location: Location,
+    },
+    /// A statement refers to a node that has not been declared
+    UnknownNode {
+        id: String,
+        span: Span,
+        location: Location,
+    },
+    /// A node or subgraph is defined again in a way that conflicts with its first definition
+    Duplicate {
+        /// What was defined, such as `node` or `subgraph`
+        kind: String,
+        id: String,
+        /// The start of the first definition
+        previous: Location,
+        span: Span,
+        location: Location,
+    },
+    /// An attribute has a name or value that is not recognised
+    InvalidAttribute {
+        name: String,
+        message: String,
+        span: Span,
+        location: Location,
+    },
+    /// The graph could not be laid out
+    Layout { message: String },
+    /// The graph could not be drawn in the requested format
+    Render { message: String },
+}
+
+impl Error {
+    pub(crate) fn syntax(kind: SyntaxKind, message: impl Into<String>, span: pest::Span) -> Error {
+        Error::Syntax {
+            kind,
+            message: message.into(),
+            span: Span::from(span),
+            location: Location::from(span.start_pos()),
+        }
+    }
+
+    pub(crate) fn unknown_node(id: impl Into<String>, span: pest::Span) -> Error {
+        Error::UnknownNode {
+            id: id.into(),
+            span: Span::from(span),
+            location: Location::from(span.start_pos()),
+        }
+    }
+
+    pub(crate) fn duplicate(
+        kind: impl Into<String>,
+        id: impl Into<String>,
+        previous: pest::Span,
+        span: pest::Span,
+    ) -> Error {
+        Error::Duplicate {
+            kind: kind.into(),
+            id: id.into(),
+            previous: Location::from(previous.start_pos()),
+            span: Span::from(span),
+            location: Location::from(span.start_pos()),
+        }
+    }
+
+    pub(crate) fn invalid_attribute(
+        name: impl Into<String>,
+        message: impl Into<String>,
+        span: pest::Span,
+    ) -> Error {
+        Error::InvalidAttribute {
+            name: name.into(),
+            message: message.into(),
+            span: Span::from(span),
+            location: Location::from(span.start_pos()),
+        }
+    }
+
+    /// The bytes of the input the error refers to, if any
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Error::Syntax { span, .. }
+            | Error::UnknownNode { span, .. }
+            | Error::Duplicate { span, .. }
+            | Error::InvalidAttribute { span, .. } => Some(*span),
+            Error::Layout { .. } | Error::Render { .. } => None,
+        }
+    }
+
+    /// The line and column of the start of the error, if any
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Error::Syntax { location, .. }
+            | Error::UnknownNode { location, .. }
+            | Error::Duplicate { location, .. }
+            | Error::InvalidAttribute { location, .. } => Some(*location),
+            Error::Layout { .. } | Error::Render { .. } => None,
+        }
+    }
+
+    /// A description of the error without its location
+    pub fn message(&self) -> String {
+        match self {
+            Error::Syntax { message, .. } => message.clone(),
+            Error::UnknownNode { id, .. } => format!("unknown node `{id}`"),
+            Error::Duplicate {
+                kind, id, previous, ..
+            } => format!(
+                "{kind} `{id}` is already defined at {}:{}",
+                previous.line, previous.column
+            ),
+            Error::InvalidAttribute { name, message, .. } => {
+                format!("invalid attribute `{name}`: {message}")
+            }
+            Error::Layout { message } | Error::Render { message } => message.clone(),
+        }
+    }
+
+    /// A suggestion for how to fix the error, if there is a likely one
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            Error::Syntax { kind, .. } => match kind {
+                SyntaxKind::Unexpected => None,
+                SyntaxKind::MissingTarget => Some(String::from(
+                    "put the target node after the arrow, such as `a --> b` or `a --> |label| b`",
+                )),
+                SyntaxKind::UnclosedSubgraph => Some(String::from(
+                    "close the subgraph with a `}` on a line of its own",
+                )),
+                SyntaxKind::NestedSubgraph => Some(String::from(
+                    "close the outer subgraph before starting another one",
+                )),
+            },
+            Error::UnknownNode { id, .. } => Some(format!(
+                "there is no node `{id}` anywhere in the graph, add one such as `{id}[Label]`"
+            )),
+            Error::Duplicate { kind, .. } if kind == "node" => Some(String::from(
+                "define the node once, or give every definition the same label and shape",
+            )),
+            Error::Duplicate { kind, .. } => Some(format!("give each {kind} a different id")),
+            Error::InvalidAttribute { name, .. } if name == "class" => Some(String::from(
+                "define the class first, such as `classDef important fill:#f9f`",
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location() {
+            Some(Location { line, column }) => write!(f, "{line}:{column}: {}", self.message()),
+            None => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<pest::Span<'_>> for Span {
+    fn from(span: pest::Span) -> Span {
+        Span {
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+impl From<pest::Position<'_>> for Location {
+    fn from(position: pest::Position) -> Location {
+        let (line, column) = position.line_col();
+        Location { line, column }
+    }
+}
+
+/// A readable name for a rule in the grammar to use in messages
+fn describe(rule: &Rule) -> String {
+    let description = match rule {
+        Rule::EOI => "end of input",
+        Rule::NEWLINE => "a new line",
+        Rule::graph | Rule::definition => "`graph`",
+        Rule::direction => "a direction such as `down`",
+        Rule::theme | Rule::theme_name => "a theme such as `theme:dark`",
+        Rule::id => "an id",
+        Rule::node | Rule::missing_target => "a node",
+        Rule::node_shape => "a label such as `[A]`",
+        Rule::line | Rule::source_head | Rule::target_head => "an arrow such as `-->`",
+        Rule::edge_label => "an edge label such as `|A|`",
+        Rule::unclosed => "`}`",
+        Rule::style_properties | Rule::style_property | Rule::style_name => {
+            "a style property such as `fill:#f9f`"
+        }
+        Rule::edge_index => "an edge index",
+        Rule::class_name => "a class name",
+        rule => return format!("{rule:?}").replace('_', " "),
+    };
+    String::from(description)
+}
+
+/// Lists the descriptions of the rules like `a, b, or c`, leaving out repeated descriptions
+fn enumerate(rules: &[Rule]) -> String {
+    let mut descriptions: Vec<String> = Vec::new();
+    for description in rules.iter().map(describe) {
+        if !descriptions.contains(&description) {
+            descriptions.push(description);
+        }
+    }
+    match descriptions.as_slice() {
+        [] => String::new(),
+        [description] => description.clone(),
+        [first, second] => format!("{first} or {second}"),
+        [rest @ .., last] => format!("{}, or {last}", rest.join(", ")),
+    }
+}
+
+impl From<pest::error::Error<Rule>> for Error {
+    fn from(error: pest::error::Error<Rule>) -> Error {
+        let (start, end) = match error.location {
+            InputLocation::Pos(position) => (position, position),
+            InputLocation::Span(span) => span,
+        };
+        let (line, column) = match error.line_col {
+            LineColLocation::Pos(position) => position,
+            LineColLocation::Span(start, _) => start,
+        };
+        let message = match error.variant {
+            ErrorVariant::ParsingError {
+                positives,
+                negatives,
+            } => match (enumerate(&positives), enumerate(&negatives)) {
+                (expected, unexpected) if unexpected.is_empty() => format!("expected {expected}"),
+                (expected, unexpected) if expected.is_empty() => format!("unexpected {unexpected}"),
+                (expected, unexpected) => format!("unexpected {unexpected}; expected {expected}"),
+            },
+            ErrorVariant::CustomError { message } => message,
+        };
+
+        Error::Syntax {
+            kind: SyntaxKind::Unexpected,
+            message,
+            span: Span { start, end },
+            location: Location { line, column },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{check_string, parse_from_string};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("grph", 1, 1)]
+    #[case("graph\n  a --> b\n  c -->", 3, 8)]
+    #[case("graph\n  a[A --> b", 2, 4)]
+    #[case("graph\n  a --> b /* unterminated", 2, 11)]
+    fn test_syntax_error_location(#[case] input: &str, #[case] line: usize, #[case] column: usize) {
+        let error = parse_from_string(input).into_result().unwrap_err();
+
+        assert!(matches!(error, Error::Syntax { .. }));
+        assert_eq!(error.location(), Some(Location { line, column }));
+        let span = error.span().unwrap();
+        assert!(span.start <= span.end && span.end <= input.len());
+    }
+
+    #[rstest]
+    #[case("grph", "expected `graph`")]
+    #[case("graph\n  a -> b", "expected end of input or an arrow such as `-->`")]
+    #[case("graph\n  a-->b", "expected end of input or a label such as `[A]`")]
+    #[case("graph\n  a -->|x| b", "expected a node")]
+    #[case(
+        "graph\n  a\n  classDef big fill:#f9f , stroke:#333",
+        "expected a style property such as `fill:#f9f`"
+    )]
+    fn test_syntax_error_message(#[case] input: &str, #[case] expected: &str) {
+        let error = parse_from_string(input).into_result().unwrap_err();
+        assert_eq!(error.message(), expected);
+    }
+
+    #[rstest]
+    #[case("graph\n  c -->", "2:8: expected")]
+    #[case("graph\n  a --> b\n  !", "3:3: expected")]
+    fn test_syntax_error_display(#[case] input: &str, #[case] expected: &str) {
+        let error = parse_from_string(input).into_result().unwrap_err();
+        assert!(error.to_string().starts_with(expected));
+        assert!(!error.message().contains('_'));
+    }
+
+    #[rstest]
+    #[case(Error::Layout { message: String::from("failed") }, "failed")]
+    #[case(
+        Error::UnknownNode {
+            id: String::from("a"),
+            span: Span { start: 6, end: 7 },
+            location: Location { line: 2, column: 1 },
+        },
+        "2:1: unknown node `a`"
+    )]
+    fn test_error_display(#[case] error: Error, #[case] expected: &str) {
+        assert_eq!(error.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case("graph\n  a --> ", Some("`a --> |label| b`"))]
+    #[case("graph\n  a -->", Some("`a --> |label| b`"))]
+    #[case("graph\n  a --> |label|", Some("`a --> |label| b`"))]
+    #[case("graph\n  subgraph s {\n    a", Some("`}`"))]
+    #[case(
+        "graph\n  subgraph s {\n    subgraph t {\n    }\n  }",
+        Some("outer subgraph")
+    )]
+    #[case("graph\n  a\n  style b fill:#f9f", Some("add one such as `b[Label]`"))]
+    #[case("graph\n  a\n  class a missing", Some("`classDef"))]
+    #[case("graph\n  a[A]\n  a(B)", Some("same label and shape"))]
+    #[case("graph\n  a --> b\n  !", None)]
+    fn test_error_hint(#[case] input: &str, #[case] expected: Option<&str>) {
+        let errors = check_string(input);
+        let hint = errors[0].hint();
+        match expected {
+            Some(expected) => assert!(hint.unwrap().contains(expected)),
+            None => assert_eq!(hint, None),
+        }
+    }
+}
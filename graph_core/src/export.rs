@@ -0,0 +1,261 @@
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::Tree,
+};
+
+use crate::{error::Error, graph::Graph, options::RenderOptions, render::usvg_options};
+
+/// The file formats a graph can be rendered to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Svg,
+    Png,
+    /// Transparent areas are drawn on white as JPEG images have no transparency
+    Jpeg,
+    /// Lossless WebP
+    WebP,
+    /// Vector PDF with the text converted to outlines, so it is drawn in the same font
+    /// everywhere
+    Pdf,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [
+        Format::Svg,
+        Format::Png,
+        Format::Jpeg,
+        Format::WebP,
+        Format::Pdf,
+    ];
+
+    /// The format of files with the given extension, ignoring case
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "svg" => Some(Format::Svg),
+            "png" => Some(Format::Png),
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "webp" => Some(Format::WebP),
+            "pdf" => Some(Format::Pdf),
+            _ => None,
+        }
+    }
+
+    /// The usual extension of files in the format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Svg => "svg",
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::WebP => "webp",
+            Format::Pdf => "pdf",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Format::Svg => "image/svg+xml",
+            Format::Png => "image/png",
+            Format::Jpeg => "image/jpeg",
+            Format::WebP => "image/webp",
+            Format::Pdf => "application/pdf",
+        }
+    }
+}
+
+/// Renders a laid out graph as a document in the given format
+pub fn render(graph: &Graph, format: Format, options: &RenderOptions) -> Result<Vec<u8>, Error> {
+    let document = crate::render_svg_with(graph, options);
+    let font_family = &graph.layout_options().font_family;
+
+    match format {
+        Format::Svg => Ok(document.into_bytes()),
+        Format::Png => rasterize(&document, font_family, options)?
+            .encode_png()
+            .map_err(|error| render_error("PNG", error)),
+        Format::Jpeg => to_jpeg(
+            &rasterize(&document, font_family, options)?,
+            options.quality,
+        ),
+        Format::WebP => to_webp(&rasterize(&document, font_family, options)?),
+        Format::Pdf => to_pdf(&document, font_family),
+    }
+}
+
+fn render_error(format: &str, error: impl std::fmt::Display) -> Error {
+    Error::Render {
+        message: format!("could not encode the {format} image: {error}"),
+    }
+}
+
+/// The most pixels a raster image can have, which takes 256MB to draw
+const MAX_PIXELS: u64 = 64_000_000;
+
+/// Draws an SVG document into a pixmap at the scale of the options
+fn rasterize(document: &str, font_family: &str, options: &RenderOptions) -> Result<Pixmap, Error> {
+    if !options.scale.is_finite() || options.scale <= 0.0 {
+        return Err(Error::Render {
+            message: format!("the scale must be a positive number, not {}", options.scale),
+        });
+    }
+    let tree =
+        Tree::from_str(document, &usvg_options(font_family)).map_err(|error| Error::Render {
+            message: format!("could not read the generated document: {error}"),
+        })?;
+
+    let size = tree.size();
+    let (width, height) = (
+        (size.width() * options.scale).ceil() as u32,
+        (size.height() * options.scale).ceil() as u32,
+    );
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(Error::Render {
+            message: format!(
+                "an image of {width}x{height} pixels is too large, use a smaller scale"
+            ),
+        });
+    }
+    let mut pixmap = Pixmap::new(width, height).ok_or_else(|| Error::Render {
+        message: format!("could not create an image of {width}x{height} pixels"),
+    })?;
+    resvg::render(
+        &tree,
+        Transform::from_scale(options.scale, options.scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+fn to_jpeg(pixmap: &Pixmap, quality: u8) -> Result<Vec<u8>, Error> {
+    let (Ok(width), Ok(height)) = (
+        u16::try_from(pixmap.width()),
+        u16::try_from(pixmap.height()),
+    ) else {
+        return Err(Error::Render {
+            message: String::from("JPEG images can be at most 65535 pixels wide and high"),
+        });
+    };
+
+    // The pixels are premultiplied by their alpha, so adding the remaining alpha draws them
+    // over white
+    let pixels = pixmap
+        .data()
+        .chunks_exact(4)
+        .flat_map(|pixel| {
+            let remaining = 255 - pixel[3];
+            [
+                pixel[0] + remaining,
+                pixel[1] + remaining,
+                pixel[2] + remaining,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut image = Vec::new();
+    jpeg_encoder::Encoder::new(&mut image, quality.clamp(1, 100))
+        .encode(&pixels, width, height, jpeg_encoder::ColorType::Rgb)
+        .map_err(|error| render_error("JPEG", error))?;
+    Ok(image)
+}
+
+fn to_webp(pixmap: &Pixmap) -> Result<Vec<u8>, Error> {
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect::<Vec<_>>();
+
+    let mut image = Vec::new();
+    image_webp::WebPEncoder::new(&mut image)
+        .encode(
+            &pixels,
+            pixmap.width(),
+            pixmap.height(),
+            image_webp::ColorType::Rgba8,
+        )
+        .map_err(|error| render_error("WebP", error))?;
+    Ok(image)
+}
+
+/// Converts an SVG document to a PDF document, with the text converted to paths
+fn to_pdf(document: &str, font_family: &str) -> Result<Vec<u8>, Error> {
+    let tree =
+        Tree::from_str(document, &usvg_options(font_family)).map_err(|error| Error::Render {
+            message: format!("could not read the generated document: {error}"),
+        })?;
+
+    let options = svg2pdf::ConversionOptions {
+        embed_text: false,
+        ..svg2pdf::ConversionOptions::default()
+    };
+    Ok(svg2pdf::to_pdf(
+        &tree,
+        options,
+        svg2pdf::PageOptions::default(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Format::Svg, b"<svg")]
+    #[case(Format::Png, b"\x89PNG")]
+    #[case(Format::Jpeg, b"\xff\xd8\xff")]
+    #[case(Format::WebP, b"RIFF")]
+    #[case(Format::Pdf, b"%PDF")]
+    fn test_render_format(#[case] format: Format, #[case] magic: &[u8]) {
+        let graph = parse("graph\n  a(A) --> b[B]").unwrap();
+        let output = render(&graph, format, &RenderOptions::default()).unwrap();
+        assert!(output.starts_with(magic));
+    }
+
+    #[test]
+    fn test_render_scale() {
+        let graph = parse("graph\n  a(A) --> b[B]").unwrap();
+        let width = |scale| {
+            let options = RenderOptions {
+                scale,
+                ..RenderOptions::default()
+            };
+            let png = render(&graph, Format::Png, &options).unwrap();
+            u32::from_be_bytes([png[16], png[17], png[18], png[19]])
+        };
+        assert!(width(2.0).abs_diff(width(1.0) * 2) <= 1);
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(-1.0)]
+    #[case(f32::NAN)]
+    #[case(f32::INFINITY)]
+    #[case(1000.0)]
+    fn test_render_scale_invalid(#[case] scale: f32) {
+        let graph = parse("graph\n  a(A) --> b[B]").unwrap();
+        let options = RenderOptions {
+            scale,
+            ..RenderOptions::default()
+        };
+        assert!(matches!(
+            render(&graph, Format::Png, &options),
+            Err(Error::Render { .. })
+        ));
+    }
+
+    #[rstest]
+    #[case("svg", Some(Format::Svg))]
+    #[case("JPEG", Some(Format::Jpeg))]
+    #[case("jpg", Some(Format::Jpeg))]
+    #[case("gif", None)]
+    fn test_format_from_extension(#[case] extension: &str, #[case] expected: Option<Format>) {
+        assert_eq!(Format::from_extension(extension), expected);
+        for format in Format::ALL {
+            assert_eq!(Format::from_extension(format.extension()), Some(format));
+        }
+    }
+}
@@ -0,0 +1,531 @@
+use pest::iterators::Pair;
+use pest::Parser;
+
+use crate::error::Error;
+use crate::node::Node;
+use crate::options::FormatOptions;
+use crate::parser::{syntax_errors, GraphParser, Rule};
+
+/// A line of the output, without the indentation of the block it is in
+#[derive(Debug, Clone)]
+struct Line {
+    /// Whether the line is separated from the line before it by a blank line
+    blank_before: bool,
+    text: String,
+}
+
+impl Line {
+    fn new(text: impl Into<String>) -> Line {
+        Line {
+            blank_before: false,
+            text: text.into(),
+        }
+    }
+}
+
+/// A statement and the comments before it
+struct Item {
+    lines: Vec<Line>,
+    /// The id of the node if the statement only declares a node
+    node_id: Option<String>,
+}
+
+/// The comments in the whitespace between two statements
+struct Trivia {
+    /// A comment on the same line as the end of the statement before the whitespace
+    trailing: Option<String>,
+    /// Comments on lines of their own
+    comments: Vec<Line>,
+    /// Whether there is a blank line after the last comment
+    blank_after: bool,
+}
+
+/// Formats the source of a graph, keeping its comments
+pub(crate) fn format_source(source: &str, options: &FormatOptions) -> Result<String, Error> {
+    let graph_pair = GraphParser::parse(Rule::graph, source)?
+        .next()
+        .expect("a graph is always parsed from the source");
+    // Statements with syntax errors are skipped by the grammar, so they would be lost
+    if let Some(error) = syntax_errors(graph_pair.clone()).into_iter().next() {
+        return Err(error);
+    }
+    let formatter = Formatter { source, options };
+
+    let mut lines = Vec::new();
+    let mut statements = Vec::new();
+    let mut header_end = 0;
+    for pair in graph_pair.into_inner() {
+        match pair.as_rule() {
+            Rule::definition => {
+                let span = pair.as_span();
+                lines.extend(trivia(&source[..span.start()], false).comments);
+                lines.extend(formatter.inner_comments(
+                    span.start(),
+                    span.end(),
+                    leaves(pair.clone()),
+                ));
+
+                let mut header = String::from("graph");
+                for definition_pair in pair.into_inner() {
+                    header.push(' ');
+                    header.push_str(definition_pair.as_str());
+                }
+                lines.push(Line::new(header));
+                header_end = span.end();
+            }
+            Rule::statement => statements.extend(pair.into_inner().last()),
+            _ => {}
+        }
+    }
+
+    let (trailing, block) = formatter.block(statements, header_end, source.len());
+    append_trailing(&mut lines, trailing);
+    lines.extend(formatter.indent(block));
+
+    let mut output = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if line.blank_before && index > 0 {
+            output.push('\n');
+        }
+        output.push_str(&line.text);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    options: &'a FormatOptions,
+}
+
+impl Formatter<'_> {
+    /// Formats the statements of a block that starts and ends at the given offsets. Returns the
+    /// comment on the line that opens the block, if any, and the lines of the block
+    fn block(
+        &self,
+        statements: Vec<Pair<Rule>>,
+        start: usize,
+        end: usize,
+    ) -> (Option<String>, Vec<Line>) {
+        let mut items: Vec<Item> = Vec::new();
+        let mut opening_comment = None;
+        let mut position = start;
+
+        for pair in statements {
+            let span = pair.as_span();
+            let Trivia {
+                trailing,
+                comments,
+                blank_after,
+            } = trivia(&self.source[position..span.start()], true);
+            match items.last_mut() {
+                Some(item) => append_trailing(&mut item.lines, trailing),
+                None => opening_comment = trailing,
+            }
+
+            let mut item = self.statement(pair);
+            if let Some(first) = item.lines.first_mut() {
+                first.blank_before = blank_after;
+            }
+            item.lines.splice(0..0, comments);
+            items.push(item);
+            position = span.end();
+        }
+
+        let Trivia {
+            trailing, comments, ..
+        } = trivia(&self.source[position..end], true);
+        match items.last_mut() {
+            Some(item) => append_trailing(&mut item.lines, trailing),
+            None => opening_comment = trailing,
+        }
+
+        if self.options.sort_nodes {
+            let (mut nodes, others): (Vec<_>, Vec<_>) =
+                items.into_iter().partition(|item| item.node_id.is_some());
+            nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+            for node in nodes.iter_mut() {
+                node.lines[0].blank_before = false;
+            }
+            let has_nodes = !nodes.is_empty();
+            items = nodes;
+            for (index, mut item) in others.into_iter().enumerate() {
+                if index == 0 && has_nodes {
+                    item.lines[0].blank_before = true;
+                }
+                items.push(item);
+            }
+        }
+
+        let mut lines = items
+            .into_iter()
+            .flat_map(|item| item.lines)
+            .chain(comments)
+            .collect::<Vec<_>>();
+        // Blocks don't start with a blank line
+        if let Some(first) = lines.first_mut() {
+            first.blank_before = false;
+        }
+        (opening_comment, lines)
+    }
+
+    /// Formats a statement along with the comments inside it, which are moved to the lines
+    /// before it
+    fn statement(&self, pair: Pair<Rule>) -> Item {
+        let span = pair.as_span();
+        let rule = pair.as_rule();
+        if rule == Rule::subgraph {
+            return self.subgraph(pair);
+        }
+
+        let mut lines = self.inner_comments(span.start(), span.end(), leaves(pair.clone()));
+        let node_id = (rule == Rule::node).then(|| Node::from(pair.clone()).id);
+        lines.push(Line::new(match rule {
+            Rule::node => format_node(pair),
+            Rule::edge => format_edge(pair),
+            _ => format_style(pair),
+        }));
+        Item { lines, node_id }
+    }
+
+    fn subgraph(&self, pair: Pair<Rule>) -> Item {
+        let span = pair.as_span();
+        let mut header = Vec::new();
+        let mut statements = Vec::new();
+        for subgraph_pair in pair.into_inner() {
+            match subgraph_pair.as_rule() {
+                Rule::subgraph_statement => statements.extend(subgraph_pair.into_inner().last()),
+                Rule::id | Rule::subgraph_label => header.push(subgraph_pair),
+                _ => {}
+            }
+        }
+
+        let header_end = header
+            .last()
+            .map_or(span.start(), |pair| pair.as_span().end());
+        let opening = header_end + find_opening_brace(&self.source[header_end..]);
+        let mut lines = self.inner_comments(span.start(), opening, header.clone());
+
+        let mut text = String::from("subgraph");
+        for header_pair in header {
+            match header_pair.as_rule() {
+                Rule::id => text = format!("{text} {}", header_pair.as_str()),
+                _ => text = format!("{text}[{}]", header_pair.as_str().trim()),
+            }
+        }
+        lines.push(Line::new(format!("{text} {{")));
+
+        // The block ends before the closing brace, which is the last character of the subgraph
+        let (trailing, block) = self.block(statements, opening + 1, span.end() - 1);
+        append_trailing(&mut lines, trailing);
+        lines.extend(self.indent(block));
+        lines.push(Line::new("}"));
+        Item {
+            lines,
+            node_id: None,
+        }
+    }
+
+    /// The block comments between the tokens from `start` to `end`, as lines of their own
+    fn inner_comments<'i>(
+        &self,
+        start: usize,
+        end: usize,
+        leaves: Vec<Pair<'i, Rule>>,
+    ) -> Vec<Line> {
+        let mut gaps = Vec::new();
+        let mut position = start;
+        for pair in leaves {
+            let span = pair.as_span();
+            gaps.push(&self.source[position..span.start()]);
+            position = span.end();
+        }
+        gaps.push(&self.source[position..end]);
+
+        let mut comments = Vec::new();
+        for mut gap in gaps {
+            while let Some(start) = gap.find("/*") {
+                let length = gap[start..]
+                    .find("*/")
+                    .map_or(gap.len() - start, |end| end + 2);
+                comments.push(Line::new(&gap[start..start + length]));
+                gap = &gap[start + length..];
+            }
+        }
+        comments
+    }
+
+    fn indent(&self, lines: Vec<Line>) -> Vec<Line> {
+        let indent = " ".repeat(self.options.indent);
+        lines
+            .into_iter()
+            .map(|line| Line {
+                text: format!("{indent}{}", line.text),
+                ..line
+            })
+            .collect()
+    }
+}
+
+/// The tokens inside a pair, which are the pairs without pairs of their own, in order
+fn leaves(pair: Pair<Rule>) -> Vec<Pair<Rule>> {
+    let mut tokens = Vec::new();
+    for inner in pair.into_inner() {
+        match inner.clone().into_inner().next() {
+            Some(_) => tokens.extend(leaves(inner)),
+            None => tokens.push(inner),
+        }
+    }
+    tokens
+}
+
+/// Finds the comments in whitespace. A comment on the same line as the start of the whitespace
+/// is the trailing comment, unless `trailing` is false
+fn trivia(text: &str, trailing: bool) -> Trivia {
+    let mut trivia = Trivia {
+        trailing: None,
+        comments: Vec::new(),
+        blank_after: false,
+    };
+    let mut newlines = 0;
+    let mut rest = text;
+
+    while let Some(character) = rest.chars().next() {
+        let length = if rest.starts_with("//") || rest.starts_with("%%") {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            rest.find("*/").map_or(rest.len(), |end| end + 2)
+        } else {
+            if character == '\n' {
+                newlines += 1;
+            }
+            rest = &rest[character.len_utf8()..];
+            continue;
+        };
+
+        let comment = rest[..length].trim_end();
+        if trailing && newlines == 0 && trivia.trailing.is_none() && trivia.comments.is_empty() {
+            trivia.trailing = Some(String::from(comment));
+        } else {
+            trivia.comments.push(Line {
+                blank_before: newlines >= 2,
+                text: String::from(comment),
+            });
+        }
+        newlines = 0;
+        rest = &rest[length..];
+    }
+
+    trivia.blank_after = newlines >= 2;
+    trivia
+}
+
+fn append_trailing(lines: &mut [Line], trailing: Option<String>) {
+    if let (Some(line), Some(comment)) = (lines.last_mut(), trailing) {
+        line.text = format!("{} {comment}", line.text);
+    }
+}
+
+/// The offset of the brace that opens the statements of a subgraph, skipping block comments
+fn find_opening_brace(text: &str) -> usize {
+    let mut position = 0;
+    while let Some(rest) = text.get(position..) {
+        if rest.starts_with('{') || rest.is_empty() {
+            break;
+        }
+        position += match rest.starts_with("/*") {
+            true => rest.find("*/").map_or(rest.len(), |end| end + 2),
+            false => rest.chars().next().map_or(1, char::len_utf8),
+        };
+    }
+    position
+}
+
+/// Formats a node with the delimiters that match its shape and the space around its label
+/// removed. The label is kept as it is if removing the space would change the shape
+fn format_node(pair: Pair<Rule>) -> String {
+    let node = Node::from(pair.clone());
+    let (open, close) = node.shape.delimiters();
+    let label = node.label.as_deref().unwrap_or_default();
+
+    let formatted = [label.trim(), label]
+        .into_iter()
+        .map(|label| format!("{}{open}{label}{close}", node.id))
+        .find(|text| {
+            let parsed = GraphParser::parse(Rule::node, text)
+                .ok()
+                .and_then(|mut pairs| pairs.next())
+                .filter(|pair| pair.as_str() == text)
+                .map(Node::from);
+            parsed.is_some_and(|parsed| {
+                parsed.shape == node.shape
+                    && parsed.label.as_deref().map(str::trim)
+                        == node.label.as_deref().map(str::trim)
+            })
+        })
+        .unwrap_or_else(|| String::from(pair.as_str()));
+    formatted
+}
+
+/// Formats an edge with single spaces around its arrow and label
+fn format_edge(pair: Pair<Rule>) -> String {
+    let mut nodes = Vec::new();
+    let mut arrow = String::new();
+    let mut label = None;
+    for edge_pair in pair.into_inner() {
+        match edge_pair.as_rule() {
+            Rule::node => nodes.push(format_node(edge_pair)),
+            Rule::source_head | Rule::line | Rule::target_head => {
+                arrow.push_str(edge_pair.as_str())
+            }
+            Rule::edge_label => {
+                label = Some(format!("|{}|", edge_pair.as_str().trim_matches('|').trim()))
+            }
+            _ => {}
+        }
+    }
+
+    match label {
+        Some(label) => nodes.join(&format!(" {arrow} {label} ")),
+        None => nodes.join(&format!(" {arrow} ")),
+    }
+}
+
+/// Formats a style, linkStyle, classDef or class statement
+fn format_style(pair: Pair<Rule>) -> String {
+    let keyword = match pair.as_rule() {
+        Rule::style => "style",
+        Rule::link_style => "linkStyle",
+        Rule::class_def => "classDef",
+        _ => "class",
+    };
+    let mut targets = Vec::new();
+    let mut class_name = "";
+    let mut properties = Vec::new();
+    for style_pair in pair.into_inner() {
+        match style_pair.as_rule() {
+            Rule::id | Rule::edge_index => targets.push(style_pair.as_str()),
+            Rule::class_name => class_name = style_pair.as_str(),
+            Rule::style_properties => {
+                for property in style_pair.into_inner() {
+                    let parts = property.into_inner().map(|part| part.as_str().trim());
+                    properties.push(parts.collect::<Vec<_>>().join(":"));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    [
+        keyword,
+        &targets.join(","),
+        class_name,
+        &properties.join(","),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_from_string;
+    use rstest::rstest;
+    use std::time::{Duration, Instant};
+
+    fn format(source: &str) -> String {
+        format_source(source, &FormatOptions::default()).unwrap()
+    }
+
+    #[rstest]
+    #[case("graph   down\n a  -->   b", "graph down\n  a --> b\n")]
+    #[case(
+        "graph\n\ta[ A ]   -.>   |  label |   b(B)",
+        "graph\n  a[A] -.> |label| b(B)\n"
+    )]
+    #[case(
+        "graph\n  a[ A ]\n  b[/ B /]\n  c([C])",
+        "graph\n  a[A]\n  b[/B/]\n  c([C])\n"
+    )]
+    #[case("graph\n  a[ /A]", "graph\n  a[/A]\n")]
+    #[case(
+        "graph\n  style  a  fill: #f9f ,stroke:#333\n  class a,b   warn",
+        "graph\n  style a fill:#f9f,stroke:#333\n  class a,b warn\n"
+    )]
+    #[case(
+        "graph\n a\n\n\n\n b\n subgraph s[ Title ]  {\n\n      c\n   }",
+        "graph\n  a\n\n  b\n  subgraph s[Title] {\n    c\n  }\n"
+    )]
+    fn test_format(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(format(input), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "// title\ngraph // header\n  a --> b // edge\n\n  %% own line\n  c\n// end",
+        "// title\ngraph // header\n  a --> b // edge\n\n  %% own line\n  c\n  // end\n"
+    )]
+    #[case(
+        "graph\n  a /* inside */ --> b\n  subgraph s { // opening\n    /* block */ c\n  } // closing",
+        "graph\n  /* inside */\n  a --> b\n  subgraph s { // opening\n    /* block */\n    c\n  } // closing\n"
+    )]
+    fn test_format_comments(#[case] input: &str, #[case] expected: &str) {
+        assert_eq!(format(input), expected);
+    }
+
+    #[test]
+    fn test_format_sort_nodes() {
+        let options = FormatOptions {
+            sort_nodes: true,
+            ..FormatOptions::default()
+        };
+        let input = "graph\n  c --> a\n  b[B] // bee\n  a(A)";
+        assert_eq!(
+            format_source(input, &options).unwrap(),
+            "graph\n  a(A)\n  b[B] // bee\n\n  c --> a\n"
+        );
+    }
+
+    #[rstest]
+    #[case("graph down\n  a[A] --> |x| b((B))\n  subgraph s[S] {\n    c{{C}} <==> d\n  }")]
+    #[case(include_str!("../examples/basic.graph"))]
+    #[case(include_str!("../examples/big.graph"))]
+    fn test_format_is_stable(#[case] input: &str) {
+        let formatted = format(input);
+        assert_eq!(format(&formatted), formatted);
+
+        let (original, formatted) = (parse_from_string(input), parse_from_string(&formatted));
+        let original = original.graph.unwrap();
+        let formatted = formatted.graph.unwrap();
+        assert_eq!(original.nodes(), formatted.nodes());
+        assert_eq!(original.edges().len(), formatted.edges().len());
+    }
+
+    #[test]
+    fn test_format_invalid() {
+        assert!(format_source("graph\n  a -->", &FormatOptions::default()).is_err());
+    }
+
+    /// How long formatting a graph with the given number of node declarations takes
+    fn format_time(nodes: usize) -> Duration {
+        let input = (0..nodes).fold(String::from("graph"), |input, index| {
+            input + &format!("\n  n{index}[N{index}] /* {index} */")
+        });
+        let start = Instant::now();
+        format_source(&input, &FormatOptions::default()).unwrap();
+        start.elapsed()
+    }
+
+    #[test]
+    fn test_format_time_linear() {
+        // Four times the input should take about four times as long, not sixteen
+        format_time(500);
+        let (small, large) = (format_time(500), format_time(2000));
+        assert!(
+            large < small * 8,
+            "{small:?} for 500 nodes, {large:?} for 2000"
+        );
+    }
+}
@@ -5,80 +5,429 @@ use rust_sugiyama::{self, CrossingMinimization, RankingType};
 use svg::node::element::Group;
 
 use crate::{
+    cluster::Cluster,
     edge::{self, Edge},
+    error::Error,
     node::Node,
+    options::{LayoutOptions, RenderOptions, Theme},
     render::ToSvg,
 };
 
-pub(crate) struct Graph {
+/// The spacing between two vertices on the same layer used by the sugiyama layout, the
+/// coordinates it produces are divided by this to get a position in "slots"
+const LAYOUT_UNIT: usize = 100;
+
+/// How far the innermost self loop of a node extends from its outline
+const LOOP_SIZE: f32 = 20.0;
+
+/// The direction the graph flows in, set by the `graph <direction>` definition
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum Direction {
+    #[default]
+    Down,
+    Up,
+    Left,
+    Right,
+}
+
+impl From<&str> for Direction {
+    fn from(direction: &str) -> Self {
+        match direction {
+            "up" => Direction::Up,
+            "left" => Direction::Left,
+            "right" => Direction::Right,
+            _ => Direction::Down,
+        }
+    }
+}
+
+impl Direction {
+    /// Whether the layers of the graph are laid out along the x axis
+    pub(crate) fn is_horizontal(&self) -> bool {
+        matches!(self, Direction::Left | Direction::Right)
+    }
+
+    /// The outward direction of the side of a node self loops are drawn on, so they run along
+    /// the layer rather than into the next one
+    pub(crate) fn loop_side(&self) -> (f32, f32) {
+        match self.is_horizontal() {
+            true => (0.0, -1.0),
+            false => (1.0, 0.0),
+        }
+    }
+
+    /// Converts a position along a layer (cross) and a layer position (rank) into x and y
+    /// coordinates, `max_rank` is used to mirror the graph for the up and left directions
+    fn to_coordinates(self, cross: f32, rank: f32, max_rank: f32) -> (f32, f32) {
+        match self {
+            Direction::Down => (cross, rank),
+            Direction::Up => (cross, max_rank - rank),
+            Direction::Right => (rank, cross),
+            Direction::Left => (max_rank - rank, cross),
+        }
+    }
+}
+
+/// A laid out graph of nodes connected by edges, produced by parsing or by a [`GraphBuilder`].
+/// After modifying the graph call [`Graph::layout`] to update the positions of its elements
+#[derive(Debug, Clone)]
+pub struct Graph {
+    direction: Direction,
+    layout: LayoutOptions,
+    theme: Option<Theme>,
     nodes: Vec<Node>,
     edges: Vec<Edge>,
+    clusters: Vec<Cluster>,
 }
 
 impl Graph {
-    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>) -> Self {
-        Graph { nodes, edges }
+    pub(crate) fn new(
+        direction: Direction,
+        layout: LayoutOptions,
+        theme: Option<Theme>,
+        nodes: Vec<Node>,
+        edges: Vec<Edge>,
+        clusters: Vec<Cluster>,
+    ) -> Self {
+        Graph {
+            direction,
+            layout,
+            theme,
+            nodes,
+            edges,
+            clusters,
+        }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) -> &mut Self {
+        self.direction = direction;
+        self
+    }
+
+    /// The options the graph is laid out with
+    pub fn layout_options(&self) -> &LayoutOptions {
+        &self.layout
+    }
+
+    pub fn set_layout_options(&mut self, options: LayoutOptions) -> &mut Self {
+        self.layout = options;
+        self
+    }
+
+    /// The theme selected by the graph, which is used unless a theme is set in the options it
+    /// is rendered with
+    pub fn theme(&self) -> Option<&Theme> {
+        self.theme.as_ref()
+    }
+
+    pub fn set_theme(&mut self, theme: Option<Theme>) -> &mut Self {
+        self.theme = theme;
+        self
+    }
+
+    /// The nodes of the graph in the order they were declared
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn nodes_mut(&mut self) -> &mut [Node] {
+        &mut self.nodes
+    }
+
+    pub fn node(&self, id: &str) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn node_mut(&mut self, id: &str) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|node| node.id == id)
+    }
+
+    /// Adds a node to the graph, replacing the node with the same id if there is one
+    pub fn add_node(&mut self, node: Node) -> &mut Self {
+        match self.node_mut(&node.id) {
+            Some(existing) => *existing = node,
+            None => self.nodes.push(node),
+        }
+        self
+    }
+
+    /// Removes a node along with the edges connected to it
+    pub fn remove_node(&mut self, id: &str) -> Option<Node> {
+        let index = self.nodes.iter().position(|node| node.id == id)?;
+        self.edges
+            .retain(|edge| edge.source != id && edge.target != id);
+        for cluster in self.clusters.iter_mut() {
+            cluster.nodes.retain(|node_id| node_id != id);
+        }
+        Some(self.nodes.remove(index))
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn edges_mut(&mut self) -> &mut [Edge] {
+        &mut self.edges
+    }
+
+    /// Adds an edge to the graph, the nodes it connects must already be in the graph
+    pub fn add_edge(&mut self, edge: Edge) -> &mut Self {
+        self.edges.push(edge);
+        self
+    }
+
+    /// Removes the edges for which `predicate` returns false
+    pub fn retain_edges(&mut self, predicate: impl FnMut(&Edge) -> bool) -> &mut Self {
+        self.edges.retain(predicate);
+        self
+    }
+
+    pub fn clusters(&self) -> &[Cluster] {
+        &self.clusters
+    }
+
+    pub fn clusters_mut(&mut self) -> &mut [Cluster] {
+        &mut self.clusters
     }
 
-    pub fn get_size(&self) -> (f32, f32) {
+    /// Lays out the graph again, positioning any nodes and edges that have been added or
+    /// changed since it was built
+    pub fn layout(&mut self) -> Result<&mut Self, Error> {
+        *self = GraphBuilder::from(self.clone()).build()?;
+        Ok(self)
+    }
+
+    /// The width and height of the laid out graph
+    pub fn size(&self) -> (f32, f32) {
         let (mut width, mut height) = (100.0, 100.0);
 
-        for node in self.nodes.iter() {
-            match (node.position, node.size) {
-                (Some(position), Some(size)) => {
-                    let (x, y) = position;
-                    let (w, h) = size;
-                    width = f32::max(x as f32 + w, width);
-                    height = f32::max(y as f32 + h, height);
-                }
-                _ => (),
+        let node_bounds = self.nodes.iter().map(|node| (node.position, node.size));
+        let cluster_bounds = self
+            .clusters
+            .iter()
+            .map(|cluster| (cluster.position, cluster.size));
+        for bounds in node_bounds.chain(cluster_bounds) {
+            if let (Some((x, y)), Some((w, h))) = bounds {
+                width = f32::max(x + w, width);
+                height = f32::max(y + h, height);
             }
         }
+        for (x, y) in self.edges.iter().flat_map(|edge| edge.points()) {
+            width = f32::max(x, width);
+            height = f32::max(y, height);
+        }
 
         (width, height)
     }
 }
 
 impl ToSvg<Group> for Graph {
-    fn to_svg(&mut self) -> svg::node::element::Group {
+    fn to_svg(&mut self, options: &RenderOptions) -> svg::node::element::Group {
+        // Text is drawn with the options the graph was laid out with so it fits its outline
+        let options = RenderOptions {
+            layout: self.layout.clone(),
+            theme: Some(options.theme_for(self.theme.as_ref()).clone()),
+            ..options.clone()
+        };
+
         let mut group = svg::node::element::Group::new().set("id", "graph");
+        for cluster in self.clusters.iter_mut() {
+            group = group.add(cluster.to_svg(&options));
+        }
         for node in self.nodes.iter_mut() {
-            group = group.add(node.to_svg());
+            group = group.add(node.to_svg(&options));
         }
         for edge in self.edges.iter_mut() {
-            group = group.add(edge.to_svg());
+            group = group.add(edge.to_svg(&options));
         }
-        group.into()
+        group
     }
 }
 
-pub(crate) struct GraphBuilder {
-    node_map: HashMap<String, Node>,
-    edge_map: HashMap<(String, String), Edge>,
+/// How an edge is routed between its nodes
+enum Route {
+    /// Bend the edge so its midpoint is moved by the offset
+    Offset((f32, f32)),
+    /// Draw the edge as a loop of the given size around its node
+    Loop(f32),
 }
 
-impl GraphBuilder {
-    pub fn new() -> Self {
+/// The distance an edge is pulled back from the outline of a node to leave room for its head,
+/// markers are scaled by the width of the line
+fn head_offset(head: &edge::EdgeHead, edge: &Edge) -> f32 {
+    head.length() * edge.stroke_width() + 2.5
+}
+
+/// Sets the start and end of an edge on the outline of the nodes it connects, leaving room for
+/// the heads. A non-zero `offset` bends the edge so its midpoint is moved by the offset
+fn route_edge(source: &Node, target: &Node, edge: &mut Edge, offset: (f32, f32)) {
+    let (sx, sy) = source.center();
+    let (tx, ty) = target.center();
+
+    let source_head_offset = head_offset(&edge.source_head, edge);
+    let target_head_offset = head_offset(&edge.target_head, edge);
+
+    let normalize = |(dx, dy): (f32, f32)| {
+        let length = (dx * dx + dy * dy).sqrt();
+        match length {
+            0.0 => None,
+            _ => Some((dx / length, dy / length)),
+        }
+    };
+
+    // The edge is a quadratic curve through this control point, so its midpoint is offset
+    let control = (
+        (sx + tx) / 2.0 + offset.0 * 2.0,
+        (sy + ty) / 2.0 + offset.1 * 2.0,
+    );
+
+    // Leave the source towards the control point and arrive at the target from it
+    let (Some((sdx, sdy)), Some((tdx, tdy))) = (
+        normalize((control.0 - sx, control.1 - sy)),
+        normalize((tx - control.0, ty - control.1)),
+    ) else {
+        return;
+    };
+
+    let source_distance = source.boundary_distance(sdx, sdy) + source_head_offset;
+    let target_distance = target.boundary_distance(tdx, tdy) + target_head_offset;
+
+    let start = (sx + sdx * source_distance, sy + sdy * source_distance);
+    let end = (tx - tdx * target_distance, ty - tdy * target_distance);
+
+    edge.position = Some((start.0, start.1, end.0, end.1));
+    edge.curve = match offset {
+        (0.0, 0.0) => None,
+        // Convert the quadratic curve into the equivalent cubic one
+        _ => Some((
+            (
+                start.0 + (control.0 - start.0) * 2.0 / 3.0,
+                start.1 + (control.1 - start.1) * 2.0 / 3.0,
+            ),
+            (
+                end.0 + (control.0 - end.0) * 2.0 / 3.0,
+                end.1 + (control.1 - end.1) * 2.0 / 3.0,
+            ),
+        )),
+    };
+}
+
+/// Draws an edge from a node back to itself as a loop leaving and entering the node on the
+/// side facing `(nx, ny)`, extending `size` from the outline
+fn route_self_loop(node: &Node, edge: &mut Edge, (nx, ny): (f32, f32), size: f32) {
+    let (cx, cy) = node.center();
+    let distance = node.boundary_distance(nx, ny);
+    let (width, height) = node.size.unwrap_or((0.0, 0.0));
+
+    // The loop leaves and enters the side either side of its middle, along the tangent
+    let (tx, ty) = (-ny, nx);
+    let spread = f32::min(8.0, f32::min(width, height) / 4.0);
+    let side = (cx + nx * distance, cy + ny * distance);
+    let start = (side.0 - tx * spread, side.1 - ty * spread);
+    let end = (side.0 + tx * spread, side.1 + ty * spread);
+    let reach = (spread + size / 2.0, size * 4.0 / 3.0);
+    let c1 = (
+        side.0 + nx * reach.1 - tx * reach.0,
+        side.1 + ny * reach.1 - ty * reach.0,
+    );
+    let c2 = (
+        side.0 + nx * reach.1 + tx * reach.0,
+        side.1 + ny * reach.1 + ty * reach.0,
+    );
+
+    // Pull the ends back along the curve to leave room for the heads
+    let pull_back = |point: (f32, f32), control: (f32, f32), offset: f32| {
+        let (dx, dy) = (control.0 - point.0, control.1 - point.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        (
+            point.0 + dx / length * offset,
+            point.1 + dy / length * offset,
+        )
+    };
+    let start = pull_back(start, c1, head_offset(&edge.source_head, edge));
+    let end = pull_back(end, c2, head_offset(&edge.target_head, edge));
+
+    edge.position = Some((start.0, start.1, end.0, end.1));
+    edge.curve = Some((c1, c2));
+}
+
+/// Finds the node with the given id
+fn find_node<'a>(nodes: &'a [Node], id: &str) -> Option<&'a Node> {
+    nodes.iter().find(|node| node.id == id)
+}
+
+/// Builds a graph from nodes, edges and clusters and lays it out
+#[derive(Debug, Default)]
+pub struct GraphBuilder {
+    direction: Direction,
+    layout: LayoutOptions,
+    theme: Option<Theme>,
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    clusters: Vec<Cluster>,
+}
+
+impl From<Graph> for GraphBuilder {
+    fn from(graph: Graph) -> Self {
         GraphBuilder {
-            node_map: HashMap::new(),
-            edge_map: HashMap::new(),
+            direction: graph.direction,
+            layout: graph.layout,
+            theme: graph.theme,
+            nodes: graph.nodes,
+            edges: graph.edges,
+            clusters: graph.clusters,
         }
     }
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        GraphBuilder::default()
+    }
 
-    pub fn build(&mut self) -> Graph {
+    pub fn build(&mut self) -> Result<Graph, Error> {
         let mut raw_graph = StableGraph::new();
         let mut node_indexes: HashMap<String, NodeIndex> = HashMap::new();
 
         // TODO: Set up minimum node spacing
-        let mut max_width = 50.0;
-        let spacing = 1.5;
+        let (mut max_width, mut max_height) = (50.0, 0.0);
+        let options = self.layout.clone();
 
-        for node in self.node_map.values() {
+        for node in self.nodes.iter_mut() {
+            node.calculate_size(&options);
             let node_id = node.id.clone();
             node_indexes.insert(node.id.clone(), raw_graph.add_node(node_id));
-            max_width = f32::max(max_width, node.max_text_width());
+            max_width = f32::max(max_width, node.max_text_width(&options));
+            if let Some((width, height)) = node.size {
+                max_width = f32::max(max_width, width);
+                max_height = f32::max(max_height, height);
+            }
         }
-        for ((source_id, target_id), edge) in self.edge_map.iter() {
+        for edge in self.edges.iter_mut() {
+            edge.measure_label(&options);
+        }
+        let mut loop_extents: HashMap<&str, f32> = HashMap::new();
+        let mut max_label_size: (f32, f32) = (0.0, 0.0);
+        for edge in self.edges.iter() {
+            let (source_id, target_id) = (&edge.source, &edge.target);
+            let (label_width, label_height) = edge.label_size();
+            max_label_size = (
+                f32::max(max_label_size.0, label_width),
+                f32::max(max_label_size.1, label_height),
+            );
+
+            // Self loops are drawn around the node so they don't take part in the layout
+            if source_id == target_id {
+                let (width, height) = edge.label_size();
+                let extent = loop_extents.entry(source_id).or_insert(LOOP_SIZE / 2.0);
+                *extent += LOOP_SIZE / 2.0 + f32::max(width, height);
+                continue;
+            }
+
             let source_index = node_indexes.get(source_id);
             let target_index = node_indexes.get(target_id);
 
@@ -86,12 +435,41 @@ impl GraphBuilder {
                 (Some(source), Some(target)) => {
                     raw_graph.add_edge(*source, *target, edge);
                 }
-                _ => println!("Edge must have source and target nodes"),
+                _ => {
+                    return Err(Error::Layout {
+                        message: format!(
+                            "edge from `{source_id}` to `{target_id}` refers to a node that does not exist"
+                        ),
+                    })
+                }
             }
         }
 
+        // The extent of a node along a layer and across layers depends on the direction
+        let (cross_extent, rank_extent) = match self.direction.is_horizontal() {
+            true => (max_height, max_width),
+            false => (max_width, max_height),
+        };
+        let mut cross_step = f32::max(
+            cross_extent * options.node_spacing,
+            cross_extent + options.node_gap,
+        );
+        if !self.clusters.is_empty() {
+            // Leave room for the outline of neighbouring clusters
+            cross_step += options.cluster_padding * 2.0;
+        }
+        // Leave room for self loops, which are drawn along the layer
+        cross_step += loop_extents.values().fold(0.0, |a, b| f32::max(a, *b));
+        // Leave room for the labels of edges running between the layers, with space for the
+        // heads of the edge either side of them
+        let label_extent = match self.direction.is_horizontal() {
+            true => max_label_size.0,
+            false => max_label_size.1,
+        };
+        let rank_step = rank_extent + f32::max(options.rank_gap, label_extent + 30.0);
+
         let layouts = rust_sugiyama::from_graph(&raw_graph)
-            .vertex_spacing((max_width * spacing) as usize)
+            .vertex_spacing(LAYOUT_UNIT)
             .minimum_length(1)
             .crossing_minimization(CrossingMinimization::Barycenter)
             .layering_type(RankingType::MinimizeEdgeLength)
@@ -100,7 +478,7 @@ impl GraphBuilder {
             .map(|(layout, width, height)| {
                 let mut new_layout = HashMap::new();
                 for (id, coords) in layout {
-                    if let Some(node) = raw_graph.node_weight(NodeIndex::from(id)) {
+                    if let Some(node) = raw_graph.node_weight(id) {
                         new_layout.insert(node, coords);
                     }
                 }
@@ -108,71 +486,621 @@ impl GraphBuilder {
             })
             .collect::<Vec<_>>();
 
-        for (positions, _, _) in layouts {
-            for (node_id, position) in positions {
-                if let Some(node) = self.node_map.get_mut(node_id) {
-                    node.position = Some((position.0 as f32, -position.1 as f32));
-                    node.calculate_size();
+        // Convert the layout of each connected component into (cross, rank) slots, placing
+        // the components next to each other along the layers
+        let mut slots = HashMap::new();
+        let mut cross_offset = 0.0;
+        let mut max_rank: f32 = 0.0;
+        for (positions, _, _) in layouts.iter() {
+            let min_cross = positions.values().map(|(x, _)| *x).min().unwrap_or(0);
+            let min_rank = positions.values().map(|(_, y)| -*y).min().unwrap_or(0);
+            let mut max_cross: f32 = 0.0;
+            for (node_id, (x, y)) in positions {
+                let cross = (x - min_cross) as f32 / LAYOUT_UNIT as f32;
+                let rank = (-y - min_rank) as f32 / LAYOUT_UNIT as f32;
+                max_cross = f32::max(max_cross, cross);
+                max_rank = f32::max(max_rank, rank);
+                slots.insert(node_id.to_string(), (cross_offset + cross, rank));
+            }
+            cross_offset += max_cross + 1.0;
+        }
+        self.group_clusters(&mut slots);
+
+        for (node_id, (cross, rank)) in slots {
+            if let Some(node) = self.nodes.iter_mut().find(|node| node.id == node_id) {
+                let (cx, cy) = self.direction.to_coordinates(
+                    cross * cross_step,
+                    rank * rank_step,
+                    max_rank * rank_step,
+                );
+                let (width, height) = node.size.unwrap_or((0.0, 0.0));
+                node.position = Some((cx - width / 2.0, cy - height / 2.0));
+            }
+        }
+
+        self.fit_clusters();
+
+        // Group the edges between the same pair of nodes so parallel edges can be spread apart
+        let mut parallel_edges: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+        let mut pairs = Vec::new();
+        for (index, edge) in self.edges.iter().enumerate() {
+            let key = match edge.source <= edge.target {
+                true => (edge.source.as_str(), edge.target.as_str()),
+                false => (edge.target.as_str(), edge.source.as_str()),
+            };
+            if !parallel_edges.contains_key(&key) {
+                pairs.push(key);
+            }
+            parallel_edges.entry(key).or_default().push(index);
+        }
+
+        let mut routes = Vec::new();
+        for (first_id, second_id) in pairs {
+            let indexes = parallel_edges
+                .remove(&(first_id, second_id))
+                .unwrap_or_default();
+            let (Some(first), Some(second)) = (
+                find_node(&self.nodes, first_id),
+                find_node(&self.nodes, second_id),
+            ) else {
+                continue;
+            };
+
+            // Self loops are nested around each other on the same side of the node
+            if first_id == second_id {
+                let (nx, ny) = self.direction.loop_side();
+                let mut size = LOOP_SIZE;
+                for index in indexes {
+                    routes.push((index, Route::Loop(size)));
+                    let (width, height) = self.edges[index].label_size();
+                    size += LOOP_SIZE / 2.0 + nx.abs() * width + ny.abs() * height;
+                }
+                continue;
+            }
+
+            // Parallel edges are offset perpendicular to the line between the nodes, far enough
+            // apart that their labels don't overlap
+            let (fx, fy) = first.center();
+            let (sx, sy) = second.center();
+            let length = f32::max(((sx - fx).powi(2) + (sy - fy).powi(2)).sqrt(), f32::EPSILON);
+            let (nx, ny) = (-(sy - fy) / length, (sx - fx) / length);
+            let edge_spacing = indexes
+                .iter()
+                .map(|index| {
+                    let (width, height) = self.edges[*index].label_size();
+                    nx.abs() * width + ny.abs() * height + 4.0
+                })
+                .fold(16.0, f32::max);
+
+            let count = indexes.len() as f32;
+            for (position, index) in indexes.into_iter().enumerate() {
+                let offset = (position as f32 - (count - 1.0) / 2.0) * edge_spacing;
+                routes.push((index, Route::Offset((nx * offset, ny * offset))));
+            }
+        }
+
+        for (index, route) in routes {
+            let edge = &mut self.edges[index];
+            if let (Some(source), Some(target)) = (
+                find_node(&self.nodes, &edge.source),
+                find_node(&self.nodes, &edge.target),
+            ) {
+                match route {
+                    Route::Offset(offset) => route_edge(source, target, edge, offset),
+                    Route::Loop(size) => {
+                        route_self_loop(source, edge, self.direction.loop_side(), size)
+                    }
                 }
             }
         }
-        for ((source_id, target_id), edge) in self.edge_map.iter_mut() {
-            if let Some(source) = self.node_map.get(source_id) {
-                if let Some(target) = self.node_map.get(target_id) {
-                    let (sx, sy) = source.position.unwrap();
-                    let (sw, sh) = source.size.unwrap();
-                    let (tx, ty) = target.position.unwrap();
-                    let (tw, th) = target.size.unwrap();
-
-                    let source_head_offset = match edge.source_head {
-                        edge::EdgeHead::Left | edge::EdgeHead::Right => 7.5,
-                        _ => 3.0,
-                    };
-                    let target_head_offset = match edge.target_head {
-                        edge::EdgeHead::Left | edge::EdgeHead::Right => 7.5,
-                        _ => 3.0,
-                    };
-
-                    // Calculate the direction of the edge
-                    let dx = tx - sx - f32::abs(source_head_offset - target_head_offset);
-                    let dy = ty - sy - f32::abs(source_head_offset - target_head_offset);
-                    let length = (dx * dx + dy * dy).sqrt();
-
-                    // Normalize the direction
-                    let dx = dx / length;
-                    let dy = dy / length;
-
-                    // Calculate the new start and end positions
-                    let start_x = sx + sw / 2.0 + dx * (sw / 2.0 + source_head_offset);
-                    let start_y = sy + sh / 2.0 + (dy * sh / 2.0 + source_head_offset);
-                    let end_x = tx + tw / 2.0 - (dx * tw / 2.0 + target_head_offset);
-                    let end_y = ty + th / 2.0 - (dy * th / 2.0 + target_head_offset);
-
-                    edge.position = Some((start_x, start_y, end_x, end_y));
+
+        self.place_labels();
+
+        // Shift the nodes, clusters and edges so the graph starts at the origin
+        let (min_x, min_y) = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.position)
+            .chain(self.clusters.iter().filter_map(|cluster| cluster.position))
+            .chain(self.edges.iter().flat_map(|edge| edge.points()))
+            .fold((f32::MAX, f32::MAX), |(min_x, min_y), (x, y)| {
+                (f32::min(min_x, x), f32::min(min_y, y))
+            });
+        for node in self.nodes.iter_mut() {
+            if let Some((x, y)) = node.position {
+                node.position = Some((x - min_x, y - min_y));
+            }
+        }
+        for cluster in self.clusters.iter_mut() {
+            if let Some((x, y)) = cluster.position {
+                cluster.position = Some((x - min_x, y - min_y));
+            }
+        }
+        for edge in self.edges.iter_mut() {
+            edge.translate(-min_x, -min_y);
+        }
+
+        if let Some(node) = self.nodes.iter().find(|node| {
+            !node
+                .position
+                .is_some_and(|(x, y)| x.is_finite() && y.is_finite())
+        }) {
+            return Err(Error::Layout {
+                message: format!("node `{}` could not be positioned", node.id),
+            });
+        }
+
+        Ok(Graph::new(
+            self.direction,
+            self.layout.clone(),
+            self.theme.clone(),
+            self.nodes.clone(),
+            self.edges.clone(),
+            self.clusters.clone(),
+        ))
+    }
+
+    /// Maps the id of each node to the index of the cluster it belongs to, a node only belongs
+    /// to the first cluster it is mentioned in
+    fn node_clusters(&self) -> HashMap<String, usize> {
+        let mut node_clusters = HashMap::new();
+        for (index, cluster) in self.clusters.iter().enumerate() {
+            for node_id in cluster.nodes.iter() {
+                node_clusters.entry(node_id.clone()).or_insert(index);
+            }
+        }
+        node_clusters
+    }
+
+    /// Reorders the nodes within each layer so the members of a cluster are next to each other,
+    /// clusters are placed at the average position of their members
+    fn group_clusters(&self, slots: &mut HashMap<String, (f32, f32)>) {
+        let node_clusters = self.node_clusters();
+        if node_clusters.is_empty() {
+            return;
+        }
+
+        let mut anchors = vec![(0.0, 0.0); self.clusters.len()];
+        for (node_id, (cross, _)) in slots.iter() {
+            if let Some(index) = node_clusters.get(node_id) {
+                anchors[*index].0 += cross;
+                anchors[*index].1 += 1.0;
+            }
+        }
+
+        let mut layers: HashMap<usize, Vec<String>> = HashMap::new();
+        for (node_id, (_, rank)) in slots.iter() {
+            layers
+                .entry(rank.round() as usize)
+                .or_default()
+                .push(node_id.clone());
+        }
+
+        for layer in layers.values_mut() {
+            let mut crosses = layer.iter().map(|id| slots[id].0).collect::<Vec<_>>();
+            crosses.sort_by(f32::total_cmp);
+
+            let key = |node_id: &String| {
+                let cross = slots[node_id].0;
+                match node_clusters.get(node_id) {
+                    Some(index) => (anchors[*index].0 / anchors[*index].1, *index, cross),
+                    None => (cross, usize::MAX, cross),
+                }
+            };
+            layer.sort_by(|a, b| {
+                let (a, b) = (key(a), key(b));
+                a.0.total_cmp(&b.0)
+                    .then(a.1.cmp(&b.1))
+                    .then(a.2.total_cmp(&b.2))
+            });
+
+            for (node_id, cross) in layer.iter().zip(crosses) {
+                if let Some(slot) = slots.get_mut(node_id) {
+                    slot.0 = cross;
                 }
             }
         }
+    }
 
-        Graph::new(
-            self.node_map.values().cloned().collect(),
-            self.edge_map.values().cloned().collect(),
-        )
+    /// Places the label of each edge along it, moving it away from the middle of the edge when
+    /// it would overlap a node or a label that has already been placed
+    fn place_labels(&mut self) {
+        let overlaps = |a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)| {
+            a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+        };
+
+        let mut obstacles = self
+            .nodes
+            .iter()
+            .filter_map(|node| match (node.position, node.size) {
+                (Some((x, y)), Some((w, h))) => Some((x, y, x + w, y + h)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for edge in self.edges.iter_mut() {
+            if edge.label.is_none() || edge.position.is_none() {
+                continue;
+            }
+
+            let (width, height) = edge.label_size();
+            let bounds_at = |(x, y): (f32, f32)| {
+                (
+                    x - width / 2.0,
+                    y - height / 2.0,
+                    x + width / 2.0,
+                    y + height / 2.0,
+                )
+            };
+            let candidates = [0.5, 0.4, 0.6, 0.3, 0.7, 0.25, 0.75];
+            let position = candidates
+                .iter()
+                .map(|t| edge.point_at(*t))
+                .find(|position| {
+                    let bounds = bounds_at(*position);
+                    !obstacles.iter().any(|obstacle| overlaps(bounds, *obstacle))
+                })
+                .unwrap_or_else(|| edge.point_at(0.5));
+
+            edge.label_position = Some(position);
+            obstacles.extend(edge.label_bounds());
+        }
     }
 
+    /// Sizes and positions each cluster around the nodes that belong to it
+    fn fit_clusters(&mut self) {
+        let node_clusters = self.node_clusters();
+
+        for (index, cluster) in self.clusters.iter_mut().enumerate() {
+            let bounds = self
+                .nodes
+                .iter()
+                .filter(|node| node_clusters.get(&node.id) == Some(&index))
+                .filter_map(|node| match (node.position, node.size) {
+                    (Some((x, y)), Some((w, h))) => Some((x, y, x + w, y + h)),
+                    _ => None,
+                })
+                .reduce(|a, b| {
+                    (
+                        f32::min(a.0, b.0),
+                        f32::min(a.1, b.1),
+                        f32::max(a.2, b.2),
+                        f32::max(a.3, b.3),
+                    )
+                });
+
+            if let Some(bounds) = bounds {
+                cluster.fit(bounds, &self.layout);
+            }
+        }
+    }
+
+    pub fn set_direction(&mut self, direction: Direction) -> &mut Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn set_layout_options(&mut self, options: LayoutOptions) -> &mut Self {
+        self.layout = options;
+        self
+    }
+
+    pub fn set_theme(&mut self, theme: Option<Theme>) -> &mut Self {
+        self.theme = theme;
+        self
+    }
+
+    pub(crate) fn node_mut(&mut self, id: &str) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|node| node.id == id)
+    }
+
+    pub(crate) fn edges_mut(&mut self) -> &mut [Edge] {
+        &mut self.edges
+    }
+
+    pub fn add_cluster(&mut self, cluster: Cluster) -> &mut Self {
+        self.clusters.push(cluster);
+        self
+    }
+
+    /// Adds a node unless there is already a node with the same id
     pub fn insert_node(&mut self, node: Node) -> &mut Self {
-        if !self.node_map.contains_key(&node.id) {
-            self.node_map.insert(node.id.clone(), node);
+        if find_node(&self.nodes, &node.id).is_none() {
+            self.nodes.push(node);
         }
         self
     }
 
+    /// Adds a node, replacing the node with the same id if there is one
     pub fn insert_or_update_node(&mut self, node: Node) -> &mut Self {
-        self.node_map.insert(node.id.clone(), node);
+        match self
+            .nodes
+            .iter_mut()
+            .find(|existing| existing.id == node.id)
+        {
+            Some(existing) => *existing = node,
+            None => self.nodes.push(node),
+        }
         self
     }
 
-    pub fn add_edge(&mut self, source: String, target: String, edge: Edge) -> &mut Self {
-        self.edge_map.insert((source, target), edge);
+    /// Adds an edge, the nodes it connects must be added before the graph is built
+    pub fn add_edge(&mut self, edge: Edge) -> &mut Self {
+        self.edges.push(edge);
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node::NodeShape;
+    use crate::parser::parse_from_string;
+    use rstest::rstest;
+
+    fn get_center(graph: &Graph, id: &str) -> (f32, f32) {
+        graph
+            .nodes
+            .iter()
+            .find(|node| node.id == id)
+            .unwrap()
+            .center()
+    }
+
+    #[rstest]
+    #[case("down", Direction::Down)]
+    #[case("up", Direction::Up)]
+    #[case("left", Direction::Left)]
+    #[case("right", Direction::Right)]
+    #[case("sideways", Direction::Down)]
+    fn test_direction_from_str(#[case] input: &str, #[case] expected: Direction) {
+        assert_eq!(Direction::from(input), expected);
+    }
+
+    #[rstest]
+    #[case("graph\n  a --> b", Direction::Down)]
+    #[case("graph down\n  a --> b", Direction::Down)]
+    #[case("graph up\n  a --> b", Direction::Up)]
+    #[case("graph right\n  a --> b", Direction::Right)]
+    #[case("graph left\n  a --> b", Direction::Left)]
+    fn test_build_direction(#[case] input: &str, #[case] direction: Direction) {
+        let graph = parse_from_string(input).into_result().unwrap();
+        let (ax, ay) = get_center(&graph, "a");
+        let (bx, by) = get_center(&graph, "b");
+
+        match direction {
+            Direction::Down => assert!(ax == bx && by > ay),
+            Direction::Up => assert!(ax == bx && by < ay),
+            Direction::Right => assert!(ay == by && bx > ax),
+            Direction::Left => assert!(ay == by && bx < ax),
+        }
+    }
+
+    #[test]
+    fn test_build_clusters_are_contiguous() {
+        let input = "graph down
+  r --> a
+  r --> b
+  r --> c
+  r --> d
+  subgraph g[Group] {
+    a
+    c
+  }";
+        let graph = parse_from_string(input).into_result().unwrap();
+        let (ax, _) = get_center(&graph, "a");
+        let (bx, _) = get_center(&graph, "b");
+        let (cx, _) = get_center(&graph, "c");
+        let (dx, _) = get_center(&graph, "d");
+        let (min_x, max_x) = (f32::min(ax, cx), f32::max(ax, cx));
+
+        assert!(!(min_x < bx && bx < max_x));
+        assert!(!(min_x < dx && dx < max_x));
+    }
+
+    #[test]
+    fn test_build_cluster_contains_members() {
+        let input = "graph right
+  subgraph g[Group] {
+    a --> b
+  }
+  b --> c";
+        let graph = parse_from_string(input).into_result().unwrap();
+        let cluster = &graph.clusters[0];
+        let (x, y) = cluster.position.unwrap();
+        let (width, height) = cluster.size.unwrap();
+
+        assert_eq!(cluster.nodes, vec!["a", "b"]);
+        for node in graph.nodes.iter().filter(|node| node.id != "c") {
+            let (nx, ny) = node.position.unwrap();
+            let (nw, nh) = node.size.unwrap();
+            assert!(x < nx && nx + nw < x + width);
+            assert!(y < ny && ny + nh < y + height);
+        }
+    }
+
+    #[rstest]
+    #[case("graph down\n  a --> |one| b\n  a --> |two| b\n  a --> |three| b")]
+    #[case("graph right\n  a --> |open| b\n  b --> |close| a")]
+    fn test_build_parallel_edges(#[case] input: &str) {
+        let graph = parse_from_string(input).into_result().unwrap();
+        let count = input.lines().count() - 1;
+        assert_eq!(graph.edges.len(), count);
+
+        let midpoints = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                let (x1, y1, x2, y2) = edge.position.unwrap();
+                let ((c1x, c1y), (c2x, c2y)) = edge.curve.unwrap_or(((x1, y1), (x2, y2)));
+                (
+                    (x1 + 3.0 * c1x + 3.0 * c2x + x2) / 8.0,
+                    (y1 + 3.0 * c1y + 3.0 * c2y + y2) / 8.0,
+                )
+            })
+            .collect::<Vec<_>>();
+        for (index, (x, y)) in midpoints.iter().enumerate() {
+            for (other_x, other_y) in midpoints.iter().skip(index + 1) {
+                let distance = ((x - other_x).powi(2) + (y - other_y).powi(2)).sqrt();
+                assert!(distance >= 16.0);
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("graph down\n  a --> a", Direction::Down)]
+    #[case("graph right\n  a --> |retry| a\n  a --> b", Direction::Right)]
+    #[case("graph left\n  a --> a\n  a --> |again| a", Direction::Left)]
+    fn test_build_self_loops(#[case] input: &str, #[case] direction: Direction) {
+        let graph = parse_from_string(input).into_result().unwrap();
+        let node = graph.nodes.iter().find(|node| node.id == "a").unwrap();
+        let (cx, cy) = node.center();
+        let (nx, ny) = direction.loop_side();
+
+        let loops = graph.edges.iter().filter(|edge| edge.curve.is_some());
+        let mut extents = Vec::new();
+        for edge in loops {
+            let (x1, y1, x2, y2) = edge.position.unwrap();
+            assert!([x1, y1, x2, y2].iter().all(|value| value.is_finite()));
+
+            // The loop bulges out of the side of the node facing the loop side
+            let ((c1x, c1y), (c2x, c2y)) = edge.curve.unwrap();
+            let extent = ((c1x + c2x) / 2.0 - cx) * nx + ((c1y + c2y) / 2.0 - cy) * ny;
+            assert!(extent > 0.0);
+            extents.push(extent);
+        }
+        assert_eq!(extents.len(), input.matches("a --> |again| a").count() + 1);
+        if let [first, second] = extents[..] {
+            assert_ne!(first, second);
+        }
+    }
+
+    #[rstest]
+    #[case("graph down\n  a --> |first label| b\n  a --> |second| c\n  b --> |third label| c")]
+    #[case("graph right\n  a --> |a long label for the edge| b\n  a --> |another| b")]
+    #[case("graph down\n  a --> |loop| a\n  a --> |exit| b")]
+    fn test_build_edge_labels(#[case] input: &str) {
+        let graph = parse_from_string(input).into_result().unwrap();
+        let overlaps = |a: (f32, f32, f32, f32), b: (f32, f32, f32, f32)| {
+            a.0 < b.2 && b.0 < a.2 && a.1 < b.3 && b.1 < a.3
+        };
+
+        let labels = graph
+            .edges
+            .iter()
+            .map(|edge| edge.label_bounds().unwrap())
+            .collect::<Vec<_>>();
+        for (index, label) in labels.iter().enumerate() {
+            for node in graph.nodes.iter() {
+                let (x, y) = node.position.unwrap();
+                let (w, h) = node.size.unwrap();
+                assert!(!overlaps(*label, (x, y, x + w, y + h)));
+            }
+            for other in labels.iter().skip(index + 1) {
+                assert!(!overlaps(*label, *other));
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("graph down\n  a --> b")]
+    #[case("graph up\n  a --> b")]
+    #[case("graph left\n  a --> b")]
+    #[case("graph right\n  a --> b")]
+    fn test_build_edge_points_at_target(#[case] input: &str) {
+        let graph = parse_from_string(input).into_result().unwrap();
+        let (ax, ay) = get_center(&graph, "a");
+        let (bx, by) = get_center(&graph, "b");
+        let (x1, y1, x2, y2) = graph.edges[0].position.unwrap();
+
+        // The edge must run in the same direction as the nodes it connects
+        assert!((x2 - x1) * (bx - ax) >= 0.0);
+        assert!((y2 - y1) * (by - ay) >= 0.0);
+        assert!(x1.is_finite() && y1.is_finite() && x2.is_finite() && y2.is_finite());
+    }
+
+    #[test]
+    fn test_builder_without_parsing() {
+        let mut node = Node::new("db");
+        node.label = Some(String::from("Database"));
+        node.shape = NodeShape::Cylinder;
+        let mut cluster = Cluster::new("backend");
+        cluster.nodes = vec![String::from("api"), String::from("db")];
+
+        let graph = GraphBuilder::new()
+            .set_direction(Direction::Right)
+            .insert_node(Node::new("api"))
+            .insert_node(node)
+            .add_edge(Edge::new("api", "db"))
+            .add_cluster(cluster)
+            .build()
+            .unwrap();
+
+        assert_eq!(graph.direction(), Direction::Right);
+        assert_eq!(
+            graph
+                .nodes()
+                .iter()
+                .map(|node| node.id.as_str())
+                .collect::<Vec<_>>(),
+            ["api", "db"]
+        );
+        assert_eq!(graph.node("db").unwrap().shape, NodeShape::Cylinder);
+        assert!(get_center(&graph, "api").0 < get_center(&graph, "db").0);
+        assert!(graph.edges()[0].position().is_some());
+        assert!(graph.clusters()[0].size().is_some());
+    }
+
+    #[test]
+    fn test_builder_unknown_node() {
+        let result = GraphBuilder::new()
+            .insert_node(Node::new("a"))
+            .add_edge(Edge::new("a", "b"))
+            .build();
+
+        assert!(matches!(result, Err(Error::Layout { .. })));
+    }
+
+    #[test]
+    fn test_graph_modify_and_layout() {
+        let mut graph = parse_from_string("graph\n  a --> b\n  b --> c")
+            .into_result()
+            .unwrap();
+        assert_eq!(graph.edges()[0].source, "a");
+        assert_eq!(graph.edges()[0].target, "b");
+
+        graph.node_mut("a").unwrap().label = Some(String::from("A much longer label"));
+        graph.add_node(Node::new("d")).add_edge(Edge::new("c", "d"));
+        graph.remove_node("b");
+        graph.layout().unwrap();
+
+        assert!(graph.node("b").is_none());
+        assert_eq!(graph.edges().len(), 1);
+        assert!(graph.nodes().iter().all(|node| node.position().is_some()));
+        let (width, _) = graph.node("a").unwrap().size().unwrap();
+        assert!(width > graph.node("d").unwrap().size().unwrap().0);
+    }
+
+    #[test]
+    fn test_build_with_layout_options() {
+        use crate::parser::parse_from_string_with;
+
+        let input = "graph\n  a --> b\n  a --> c";
+        let default = parse_from_string(input).into_result().unwrap();
+        let options = LayoutOptions {
+            label_font_size: 16.0,
+            node_gap: 60.0,
+            rank_gap: 120.0,
+            ..LayoutOptions::default()
+        };
+        let spaced = parse_from_string_with(input, &options)
+            .into_result()
+            .unwrap();
+
+        let (width, height) = default.size();
+        let (spaced_width, spaced_height) = spaced.size();
+        assert!(spaced_width > width);
+        assert!(spaced_height > height);
+        assert_eq!(spaced.layout_options(), &options);
+    }
+}
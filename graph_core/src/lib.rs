@@ -1,83 +1,150 @@
+mod cluster;
 mod edge;
+mod error;
+mod export;
+mod formatter;
 mod graph;
 mod node;
+mod options;
 mod parser;
 mod render;
+mod style;
 
 use base64::{engine::general_purpose, Engine as _};
-use parser::parse_from_string;
+pub use cluster::Cluster;
+pub use edge::{Edge, EdgeHead, EdgeLine};
+pub use error::{Error, Location, Span, SyntaxKind};
+pub use export::{render, Format};
+pub use graph::{Direction, Graph, GraphBuilder};
+pub use node::{Node, NodeShape};
+pub use options::{FormatOptions, LayoutOptions, RenderOptions, Theme};
+pub use parser::ParseResult;
+use parser::{check_string, parse_from_string, parse_from_string_with};
 use render::ToSvg;
+pub use style::Style;
 use svg::{
-    node::element::{Definitions, Marker, Polygon, Style},
+    node::element::{Definitions, Rectangle, Style as StyleSheet},
     Document,
 };
 
-pub(crate) trait Position {
-    fn set_position(&mut self, position: (isize, isize));
+/// Parses and lays out a graph, returning the first error in the input if there are any
+pub fn parse(contents: &str) -> Result<Graph, Error> {
+    parse_from_string(contents).into_result()
 }
 
-pub fn generate_graph(contents: &str) -> Result<String, String> {
-    match parse_from_string(&contents) {
-        Ok(mut graph) => {
-            let mut graph_group = graph.to_svg();
-
-            let (width, height) = graph.get_size();
-            let margin = f32::max(20.0, f32::max(width, height) * 0.075);
-
-            graph_group = graph_group.set("transform", format!("translate({margin}, {margin})"));
-
-            let font_data = include_bytes!("../fonts/JetBrainsMono-Light.ttf");
-            let font_data_base64 = general_purpose::STANDARD.encode(font_data);
-
-            let right_arrow = Marker::new()
-                .set("id", "right-arrow")
-                .set("markerWidth", 5)
-                .set("markerHeight", 5)
-                .set("refX", 0)
-                .set("refY", 2.5)
-                .set("orient", "auto")
-                .set("fill", "#5d5b5d")
-                .add(Polygon::new().set("points", "0 0, 5 2.5, 0 5"));
-
-            let left_arrow = Marker::new()
-                .set("id", "left-arrow")
-                .set("markerWidth", 5)
-                .set("markerHeight", 5)
-                .set("refX", 5)
-                .set("refY", 2.5)
-                .set("orient", "auto")
-                .set("fill", "#5d5b5d")
-                .add(Polygon::new().set("points", "5 0, 0 2.5, 5 5"));
-
-            let document = Document::new()
-                .set(
-                    "viewBox",
-                    (0, 0, width + margin * 2.0, height + margin * 2.0),
-                )
-                .add(
-                    Definitions::new()
-                        .add(left_arrow)
-                        .add(right_arrow)
-                        .add(Style::new(format!(
-                            "@font-face {{
-                        font-family: 'JetBrains Mono';
-                        src: url(data:font/ttf;base64,{}) format('truetype');
-                    }}
-                    text {{
-                        font-family: 'JetBrains Mono';
-                    }}
-                    ",
-                            font_data_base64
-                        ))),
-                )
-                .add(graph_group);
-
-            // background-image:
-            Ok(document.to_string())
-        }
-        Err(e) => {
-            eprintln!("{:?}", e);
-            Err(e.to_string())
-        }
+/// Parses and lays out the statements that could be parsed, along with every error in the input
+pub fn parse_with_diagnostics(contents: &str) -> ParseResult {
+    parse_from_string(contents)
+}
+
+/// Parses and validates a graph without laying it out, returning every error in the input. Faster
+/// than [`parse_with_diagnostics`] when only the errors are needed, and also reports nodes and
+/// subgraphs that are defined more than once
+pub fn check(contents: &str) -> Vec<Error> {
+    check_string(contents)
+}
+
+/// Formats the source of a graph with the default options, keeping its comments. Returns the
+/// source unchanged if it can't be parsed
+pub fn format(contents: &str) -> String {
+    format_with(contents, &FormatOptions::default()).unwrap_or_else(|_| String::from(contents))
+}
+
+/// Formats the source of a graph with the given options, returning the first syntax error if it
+/// can't be parsed
+pub fn format_with(contents: &str, options: &FormatOptions) -> Result<String, Error> {
+    formatter::format_source(contents, options)
+}
+
+pub fn generate_graph(contents: &str) -> Result<String, Error> {
+    generate_graph_with(contents, &RenderOptions::default())
+}
+
+/// Generates the graph laid out and drawn with the given options
+pub fn generate_graph_with(contents: &str, options: &RenderOptions) -> Result<String, Error> {
+    let graph = parse_from_string_with(contents, &options.layout).into_result()?;
+    Ok(render_svg_with(&graph, options))
+}
+
+/// Generates the graph from the statements that could be parsed along with every error in the
+/// input, so a preview can still be shown while the input is being edited
+pub fn generate_graph_with_diagnostics(contents: &str) -> (Option<String>, Vec<Error>) {
+    let result = parse_with_diagnostics(contents);
+    let document = result.graph.as_ref().map(render_svg);
+    (document, result.errors)
+}
+
+/// Renders a laid out graph as an SVG document
+pub fn render_svg(graph: &Graph) -> String {
+    render_svg_with(graph, &RenderOptions::default())
+}
+
+/// Renders a laid out graph as an SVG document drawn with the given options
+pub fn render_svg_with(graph: &Graph, options: &RenderOptions) -> String {
+    let mut graph = graph.clone();
+    let mut graph_group = graph.to_svg(options);
+
+    let (width, height) = graph.size();
+    let margin = options.margin((width, height));
+
+    graph_group = graph_group.set("transform", format!("translate({margin}, {margin})"));
+    let theme = options.theme_for(graph.theme());
+
+    let font_family = &graph.layout_options().font_family;
+    let mut style = String::new();
+    // Only the default font is embedded, any other font is expected to be installed
+    if font_family == options::DEFAULT_FONT_FAMILY {
+        let font_data_base64 = general_purpose::STANDARD.encode(render::FONT_DATA);
+        style.push_str(&format!(
+            "@font-face {{
+                font-family: '{font_family}';
+                src: url(data:font/ttf;base64,{font_data_base64}) format('truetype');
+            }}
+            "
+        ));
+    }
+    style.push_str(&format!(
+        "text {{
+                font-family: '{font_family}';
+                fill: {};
+            }}
+            ",
+        theme.text
+    ));
+    if let Some(dark_theme) = &options.dark_theme {
+        style.push_str(&format!(
+            "@media (prefers-color-scheme: dark) {{
+            {}}}
+            ",
+            dark_theme.to_css()
+        ));
     }
+
+    let mut definitions = Definitions::new();
+    for marker in EdgeHead::markers(theme) {
+        definitions = definitions.add(marker);
+    }
+    for marker in EdgeHead::styled_markers(graph.edges()) {
+        definitions = definitions.add(marker);
+    }
+
+    let (document_width, document_height) = (width + margin * 2.0, height + margin * 2.0);
+    let mut document = Document::new()
+        .set("viewBox", (0, 0, document_width, document_height))
+        .add(definitions.add(StyleSheet::new(style)));
+    // The background is always drawn when there is a dark theme so it can be switched on
+    let background = options.background.as_ref().or(theme.background.as_ref());
+    if background.is_some() || options.dark_theme.is_some() {
+        document = document.add(
+            Rectangle::new()
+                .set("class", "background")
+                .set("width", document_width)
+                .set("height", document_height)
+                .set("fill", background.map_or("none", String::as_str)),
+        );
+    }
+    let document = document.add(graph_group);
+
+    // background-image:
+    document.to_string()
 }
@@ -1,41 +1,175 @@
+use std::f32::consts::PI;
+
 use crate::{
+    options::{LayoutOptions, RenderOptions, Theme},
     parser::Rule,
-    render::{measure_text_width, ToSvg},
+    render::{measure_text_width, with_style, ToSvg},
+    style::Style,
 };
 use pest::iterators::Pair;
-use svg::node::element::{ClipPath, Definitions, Group, Line, Rectangle, Text};
+use svg::node::element::{
+    ClipPath, Definitions, Ellipse, Group, Line, Path, Polygon, Rectangle, Text,
+};
 
+/// A node in the graph, declared with its id followed by an optional shape and label such as
+/// `id(label)`
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) struct Node {
+pub struct Node {
     pub id: String,
     pub label: Option<String>,
     pub shape: NodeShape,
-    pub position: Option<(f32, f32)>,
-    pub size: Option<(f32, f32)>,
+    /// Colors and line styles that override the theme for this node
+    pub style: Style,
+    /// The top left corner of the node
+    pub(crate) position: Option<(f32, f32)>,
+    pub(crate) size: Option<(f32, f32)>,
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub(crate) enum NodeShape {
+#[derive(Debug, Default, PartialEq, Clone)]
+pub enum NodeShape {
+    /// `(label)`
     Rounded,
+    /// `[label]`
     Square,
+    /// `{label}`
     Triangle,
+    /// `((label))`
+    Circle,
+    /// `<label>`
+    Diamond,
+    /// `{{label}}`
+    Hexagon,
+    /// `[(label)]`
+    Cylinder,
+    /// `[/label/]`
+    Parallelogram,
+    /// `([label])`
+    Stadium,
+    #[default]
     Empty,
 }
 
-impl Default for NodeShape {
-    fn default() -> Self {
-        NodeShape::Empty
+impl NodeShape {
+    /// The height of the ellipses at the top and bottom of a cylinder
+    const CYLINDER_RADIUS: f32 = 5.0;
+
+    /// The text that opens and closes the label of a node with the shape
+    pub(crate) fn delimiters(&self) -> (&'static str, &'static str) {
+        match self {
+            NodeShape::Rounded => ("(", ")"),
+            NodeShape::Square => ("[", "]"),
+            NodeShape::Triangle => ("{", "}"),
+            NodeShape::Circle => ("((", "))"),
+            NodeShape::Diamond => ("<", ">"),
+            NodeShape::Hexagon => ("{{", "}}"),
+            NodeShape::Cylinder => ("[(", ")]"),
+            NodeShape::Parallelogram => ("[/", "/]"),
+            NodeShape::Stadium => ("([", "])"),
+            NodeShape::Empty => ("", ""),
+        }
     }
-}
 
-impl NodeShape {
-    pub fn to_svg(&self, id: &str, width: f32, height: f32, stroke: f32, header: f32) -> Group {
+    /// The size of the shape needed to fit content of the given size inside its outline
+    pub(crate) fn size(&self, (width, height): (f32, f32)) -> (f32, f32) {
+        match self {
+            NodeShape::Rounded | NodeShape::Square | NodeShape::Empty => (width, height),
+            NodeShape::Circle => {
+                let diameter = (width * width + height * height).sqrt();
+                (diameter, diameter)
+            }
+            // The corners of the content touch the sides of the diamond
+            NodeShape::Diamond => (width + height * 2.0, width / 2.0 + height),
+            // The content sits on the base, where the triangle is wide enough for it
+            NodeShape::Triangle => (width * 5.0 / 3.0, height * 2.5),
+            NodeShape::Hexagon | NodeShape::Parallelogram | NodeShape::Stadium => {
+                (width + height, height)
+            }
+            NodeShape::Cylinder => (width, height + NodeShape::CYLINDER_RADIUS * 3.0),
+        }
+    }
+
+    /// The offset of content of the given size inside a shape of the given size
+    pub(crate) fn content_offset(&self, content: (f32, f32), size: (f32, f32)) -> (f32, f32) {
+        let centered = ((size.0 - content.0) / 2.0, (size.1 - content.1) / 2.0);
+        match self {
+            NodeShape::Triangle => (centered.0, size.1 - content.1),
+            NodeShape::Cylinder => (centered.0, centered.1 + NodeShape::CYLINDER_RADIUS),
+            _ => centered,
+        }
+    }
+
+    /// The outline of the shape as a polygon relative to its top left corner, curves are
+    /// approximated by line segments
+    pub(crate) fn outline(&self, (width, height): (f32, f32)) -> Vec<(f32, f32)> {
+        let ellipse = |cx: f32, cy: f32, rx: f32, ry: f32, from: f32, to: f32| {
+            let steps = 16;
+            (0..=steps)
+                .map(|step| {
+                    let angle = from + (to - from) * step as f32 / steps as f32;
+                    (cx + rx * angle.cos(), cy + ry * angle.sin())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            NodeShape::Circle => ellipse(
+                width / 2.0,
+                height / 2.0,
+                width / 2.0,
+                height / 2.0,
+                0.0,
+                PI * 2.0,
+            ),
+            NodeShape::Diamond => vec![
+                (width / 2.0, 0.0),
+                (width, height / 2.0),
+                (width / 2.0, height),
+                (0.0, height / 2.0),
+            ],
+            NodeShape::Triangle => vec![(width / 2.0, 0.0), (width, height), (0.0, height)],
+            NodeShape::Hexagon => vec![
+                (height / 2.0, 0.0),
+                (width - height / 2.0, 0.0),
+                (width, height / 2.0),
+                (width - height / 2.0, height),
+                (height / 2.0, height),
+                (0.0, height / 2.0),
+            ],
+            NodeShape::Parallelogram => vec![
+                (height / 2.0, 0.0),
+                (width, 0.0),
+                (width - height / 2.0, height),
+                (0.0, height),
+            ],
+            NodeShape::Stadium => {
+                let radius = height / 2.0;
+                let mut points =
+                    ellipse(width - radius, radius, radius, radius, -PI / 2.0, PI / 2.0);
+                points.extend(ellipse(radius, radius, radius, radius, PI / 2.0, PI * 1.5));
+                points
+            }
+            NodeShape::Rounded | NodeShape::Square | NodeShape::Cylinder | NodeShape::Empty => {
+                vec![(0.0, 0.0), (width, 0.0), (width, height), (0.0, height)]
+            }
+        }
+    }
+
+    pub(crate) fn to_svg(
+        &self,
+        id: &str,
+        (width, height): (f32, f32),
+        stroke: f32,
+        header: f32,
+        theme: &Theme,
+        style: &Style,
+    ) -> Group {
+        let shape_style = ["fill", "stroke", "stroke-dasharray"];
         let mut group = Group::new().set("class", "node");
         match self {
             NodeShape::Rounded | NodeShape::Square => {
                 let rx = match self {
                     NodeShape::Rounded => 12.0,
-                    NodeShape::Square | _ => 4.0,
+                    _ => 4.0,
                 };
 
                 let clip = ClipPath::new().set("id", format!("clip_path_{}", id)).add(
@@ -50,39 +184,125 @@ impl NodeShape {
                 let defs = Definitions::new().add(clip);
                 group = group
                     .add(defs)
-                    .add(
+                    .add(with_style(
                         Rectangle::new()
                             .set("width", width)
                             .set("height", height)
                             .set("stroke-width", stroke)
                             .set("rx", rx)
-                            .set("fill", "#fcf9fa")
-                            .set("stroke", "#cecace"),
-                    )
-                    .add(
+                            .set("class", "node-shape")
+                            .set("fill", theme.node_fill.as_str())
+                            .set("stroke", theme.node_stroke.as_str()),
+                        style,
+                        &shape_style,
+                    ))
+                    .add(with_style(
                         Rectangle::new()
                             .set("width", width - stroke)
                             .set("height", height - stroke)
                             .set("x", stroke / 2.0)
                             .set("y", stroke / 2.0)
                             .set("rx", rx - stroke / 2.0)
-                            .set("fill", "#fcf9fa")
-                            .set(
-                                "clip-path",
-                                format!("url(#{})", format!("clip_path_{}", id)),
-                            ),
-                    )
-                    .add(
+                            .set("class", "node-fill")
+                            .set("fill", theme.node_fill.as_str())
+                            .set("clip-path", format!("url(#clip_path_{})", id)),
+                        style,
+                        &["fill"],
+                    ))
+                    .add(with_style(
                         Line::new()
                             .set("x1", 0)
                             .set("y1", header)
                             .set("x2", width)
                             .set("y2", header)
-                            .set("stroke", "#cecace")
+                            .set("class", "node-line")
+                            .set("stroke", theme.node_stroke.as_str())
                             .set("stroke-width", 1),
-                    );
+                        style,
+                        &["stroke"],
+                    ));
+            }
+            NodeShape::Circle => {
+                group = group.add(with_style(
+                    Ellipse::new()
+                        .set("cx", width / 2.0)
+                        .set("cy", height / 2.0)
+                        .set("rx", width / 2.0)
+                        .set("ry", height / 2.0)
+                        .set("stroke-width", stroke)
+                        .set("class", "node-shape")
+                        .set("fill", theme.node_fill.as_str())
+                        .set("stroke", theme.node_stroke.as_str()),
+                    style,
+                    &shape_style,
+                ));
+            }
+            NodeShape::Stadium => {
+                group = group.add(with_style(
+                    Rectangle::new()
+                        .set("width", width)
+                        .set("height", height)
+                        .set("rx", height / 2.0)
+                        .set("stroke-width", stroke)
+                        .set("class", "node-shape")
+                        .set("fill", theme.node_fill.as_str())
+                        .set("stroke", theme.node_stroke.as_str()),
+                    style,
+                    &shape_style,
+                ));
+            }
+            NodeShape::Cylinder => {
+                let (rx, ry) = (width / 2.0, NodeShape::CYLINDER_RADIUS);
+                let body = format!(
+                    "M 0 {ry} A {rx} {ry} 0 0 1 {width} {ry} L {width} {bottom} \
+                     A {rx} {ry} 0 0 1 0 {bottom} Z",
+                    bottom = height - ry
+                );
+                let rim = format!("M 0 {ry} A {rx} {ry} 0 0 0 {width} {ry}");
+                group = group
+                    .add(with_style(
+                        Path::new()
+                            .set("d", body)
+                            .set("stroke-width", stroke)
+                            .set("class", "node-shape")
+                            .set("fill", theme.node_fill.as_str())
+                            .set("stroke", theme.node_stroke.as_str()),
+                        style,
+                        &shape_style,
+                    ))
+                    .add(with_style(
+                        Path::new()
+                            .set("d", rim)
+                            .set("stroke-width", stroke)
+                            .set("class", "node-line")
+                            .set("fill", "none")
+                            .set("stroke", theme.node_stroke.as_str()),
+                        style,
+                        &["stroke"],
+                    ));
+            }
+            NodeShape::Triangle
+            | NodeShape::Diamond
+            | NodeShape::Hexagon
+            | NodeShape::Parallelogram => {
+                let points = self
+                    .outline((width, height))
+                    .iter()
+                    .map(|(x, y)| format!("{x},{y}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                group = group.add(with_style(
+                    Polygon::new()
+                        .set("points", points)
+                        .set("stroke-width", stroke)
+                        .set("stroke-linejoin", "round")
+                        .set("class", "node-shape")
+                        .set("fill", theme.node_fill.as_str())
+                        .set("stroke", theme.node_stroke.as_str()),
+                    style,
+                    &shape_style,
+                ));
             }
-            NodeShape::Triangle => {}
             NodeShape::Empty => {}
         }
 
@@ -96,84 +316,144 @@ impl From<&str> for NodeShape {
             "(" => NodeShape::Rounded,
             "[" => NodeShape::Square,
             "{" => NodeShape::Triangle,
+            "((" => NodeShape::Circle,
+            "<" => NodeShape::Diamond,
+            "{{" => NodeShape::Hexagon,
+            "[(" => NodeShape::Cylinder,
+            "[/" => NodeShape::Parallelogram,
+            "([" => NodeShape::Stadium,
             _ => NodeShape::Empty,
         }
     }
 }
 
 impl Node {
-    pub fn new() -> Node {
+    pub fn new(id: impl Into<String>) -> Node {
         Node {
-            id: String::new(),
+            id: id.into(),
             label: None,
             shape: NodeShape::Empty,
+            style: Style::default(),
             position: None,
             size: None,
         }
     }
 
-    pub fn max_text_width(&self) -> f32 {
-        let (mut max_text_width, _id_text_height) = measure_text_width(&self.id, 8.0);
+    /// The width of the text of the node measured larger than it is drawn, used to leave room
+    /// between neighbouring nodes
+    pub(crate) fn max_text_width(&self, options: &LayoutOptions) -> f32 {
+        let (mut max_text_width, _id_text_height) = measure_text_width(
+            &self.id,
+            options.id_font_size * 4.0 / 3.0,
+            &options.font_family,
+        );
 
         if let Some(label) = &self.label {
-            let (label_text_width, _label_text_height) = measure_text_width(&label, 12.0);
+            let (label_text_width, _label_text_height) =
+                measure_text_width(label, options.label_font_size * 1.5, &options.font_family);
             max_text_width = f32::max(max_text_width, label_text_width);
         }
         max_text_width
     }
 
-    pub fn calculate_size(&mut self) {
-        let padding = (10.0, 5.0);
-        let id_font_size = 6.0;
-        let label_font_size = 8.0;
-        let (id_text_width, id_text_height) = measure_text_width(&self.id, id_font_size);
+    /// The size of the id and label text of the node, returned as the size of the padded
+    /// content along with the heights of the id and label
+    fn content_size(&self, options: &LayoutOptions) -> ((f32, f32), f32, f32) {
+        let (id_text_width, id_text_height) =
+            measure_text_width(&self.id, options.id_font_size, &options.font_family);
         let (label_text_width, label_text_height) = match &self.label {
-            Some(label) => measure_text_width(&label, label_font_size),
+            Some(label) => measure_text_width(label, options.label_font_size, &options.font_family),
             None => (0.0, 0.0),
         };
 
+        let padding = options.node_padding;
         let size = (
             f32::max(id_text_width, label_text_width) + padding.0 * 2.0,
             label_text_height + id_text_height + padding.1 * 2.0,
         );
+        (size, id_text_height, label_text_height)
+    }
 
-        self.size = Some(size);
+    pub(crate) fn calculate_size(&mut self, options: &LayoutOptions) {
+        let (content, _, _) = self.content_size(options);
+        self.size = Some(self.shape.size(content));
+    }
+
+    /// The top left corner of the node, once the graph is laid out
+    pub fn position(&self) -> Option<(f32, f32)> {
+        self.position
+    }
+
+    /// The size of the node, once the graph is laid out
+    pub fn size(&self) -> Option<(f32, f32)> {
+        self.size
+    }
+
+    /// The center of the node, requires the node to have been positioned and sized
+    pub fn center(&self) -> (f32, f32) {
+        let (x, y) = self.position.unwrap_or((0.0, 0.0));
+        let (width, height) = self.size.unwrap_or((0.0, 0.0));
+        (x + width / 2.0, y + height / 2.0)
+    }
+
+    /// The distance from the center of the node to its outline along the normalised
+    /// direction `(dx, dy)`
+    pub(crate) fn boundary_distance(&self, dx: f32, dy: f32) -> f32 {
+        let (width, height) = self.size.unwrap_or((0.0, 0.0));
+        let (cx, cy) = (width / 2.0, height / 2.0);
+        let outline = self.shape.outline((width, height));
+
+        // Find where the ray from the center crosses the outline
+        let mut distance = f32::INFINITY;
+        for (index, (ax, ay)) in outline.iter().enumerate() {
+            let (bx, by) = outline[(index + 1) % outline.len()];
+            let (ex, ey) = (bx - ax, by - ay);
+            let denominator = dx * ey - dy * ex;
+            if denominator.abs() < f32::EPSILON {
+                continue;
+            }
+            let t = ((ax - cx) * ey - (ay - cy) * ex) / denominator;
+            let u = ((ax - cx) * dy - (ay - cy) * dx) / denominator;
+            if t >= 0.0 && (0.0..=1.0).contains(&u) {
+                distance = f32::min(distance, t);
+            }
+        }
+
+        match distance {
+            f32::INFINITY => 0.0,
+            _ => distance,
+        }
     }
 }
 
 impl ToSvg<Group> for Node {
-    fn to_svg(&mut self) -> Group {
+    fn to_svg(&mut self, options: &RenderOptions) -> Group {
         // Calculate sizes and positions for elements
-        let padding = (10.0, 5.0);
-        let (x, y) = match self.position {
-            Some(position) => position,
-            None => (0.0, 0.0),
-        };
-        let id_font_size = 6.0;
-        let label_font_size = 8.0;
-        let (id_text_width, id_text_height) = measure_text_width(&self.id, id_font_size);
-        let (label_text_width, label_text_height) = match &self.label {
-            Some(label) => measure_text_width(&label, label_font_size),
-            None => (0.0, 0.0),
-        };
-
-        let size = (
-            f32::max(id_text_width, label_text_width) + padding.0 * 2.0,
-            label_text_height + id_text_height + padding.1 * 2.0,
-        );
+        let padding = options.layout.node_padding;
+        let (x, y) = self.position.unwrap_or((0.0, 0.0));
+        let (content, id_text_height, label_text_height) = self.content_size(&options.layout);
+        let size = self.shape.size(content);
+        let (offset_x, offset_y) = self.shape.content_offset(content, size);
 
         let mut group = Group::new()
             .set("id", self.id.clone())
             .set("transform", format!("translate({},{})", x, y));
 
+        let text_style = ["color", "font-weight"];
         let id = Text::new(&self.id)
-            .set("font-size", format!("{}px", id_font_size))
-            .set("x", padding.0)
-            .set("y", id_text_height);
-
-        let shape = self
-            .shape
-            .to_svg(&self.id, size.0, size.1, 1.0, id_text_height + padding.1);
+            .set("font-size", format!("{}px", options.layout.id_font_size))
+            .set("x", offset_x + padding.0)
+            .set("y", offset_y + id_text_height);
+        let id = with_style(id, &self.style, &text_style);
+
+        let shape = self.shape.to_svg(
+            &self.id,
+            size,
+            self.style.stroke_width.unwrap_or(1.0),
+            id_text_height + padding.1,
+            options.theme(),
+            &self.style,
+        );
 
         group = group.add(shape).add(id).set(
             "transform",
@@ -186,11 +466,14 @@ impl ToSvg<Group> for Node {
 
         if let Some(label) = &self.label {
             let label_text = Text::new(label)
-                .set("font-size", format!("{}px", label_font_size))
-                .set("x", padding.0)
-                .set("y", id_text_height + label_text_height + padding.1);
+                .set("font-size", format!("{}px", options.layout.label_font_size))
+                .set("x", offset_x + padding.0)
+                .set(
+                    "y",
+                    offset_y + id_text_height + label_text_height + padding.1,
+                );
 
-            group = group.add(label_text);
+            group = group.add(with_style(label_text, &self.style, &text_style));
         }
 
         self.size = Some(size);
@@ -201,25 +484,28 @@ impl ToSvg<Group> for Node {
 
 impl<'a> From<Pair<'a, Rule>> for Node {
     fn from(pair: Pair<Rule>) -> Node {
-        let mut node = Node::new();
+        let mut node = Node::new("");
 
-        let node_pairs = pair.into_inner();
-        for node_pair in node_pairs {
-            let rule = node_pair.as_rule();
-            match rule {
+        for node_pair in pair.into_inner() {
+            match node_pair.as_rule() {
                 Rule::id => {
                     node.id = node_pair.as_str().to_string();
                 }
-                Rule::node_label => {
-                    let node_label = node_pair.as_str();
+                Rule::node_shape => {
+                    let shape_start = node_pair.as_span().start();
+                    let Some(label_pair) = node_pair.into_inner().next() else {
+                        continue;
+                    };
+                    let node_label = label_pair.as_str();
                     node.label = if node_label.is_empty() {
                         None
                     } else {
                         Some(String::from(node_label))
-                    }
-                }
-                Rule::node_shape => {
-                    node.shape = NodeShape::from(node_pair.as_str());
+                    };
+                    // The shape is the text that opens the label
+                    let opening =
+                        &label_pair.get_input()[shape_start..label_pair.as_span().start()];
+                    node.shape = NodeShape::from(opening);
                 }
                 _ => {}
             }
@@ -234,19 +520,23 @@ mod tests {
     use pest::Parser;
     use rstest::rstest;
 
-    fn get_node(input: &str) -> Pair<Rule> {
+    fn get_node(input: &str) -> Pair<'_, Rule> {
         use crate::parser::GraphParser;
 
-        let parse_result = GraphParser::parse(Rule::node, &input);
-        let pair = parse_result.unwrap().next().unwrap();
-
-        pair
+        let parse_result = GraphParser::parse(Rule::node, input);
+        parse_result.unwrap().next().unwrap()
     }
 
     #[rstest]
     #[case("(", NodeShape::Rounded)]
     #[case("[", NodeShape::Square)]
     #[case("{", NodeShape::Triangle)]
+    #[case("((", NodeShape::Circle)]
+    #[case("<", NodeShape::Diamond)]
+    #[case("{{", NodeShape::Hexagon)]
+    #[case("[(", NodeShape::Cylinder)]
+    #[case("[/", NodeShape::Parallelogram)]
+    #[case("([", NodeShape::Stadium)]
     #[case(")", NodeShape::Empty)]
     #[case("x", NodeShape::Empty)]
     #[case("not a shape", NodeShape::Empty)]
@@ -260,6 +550,18 @@ mod tests {
     #[case("a (a)", "a", None, NodeShape::Empty)]
     #[case("a[a]", "a", Some("a"), NodeShape::Square)]
     #[case("a{}", "a", None, NodeShape::Triangle)]
+    #[case("a{A}", "a", Some("A"), NodeShape::Triangle)]
+    #[case("a((A))", "a", Some("A"), NodeShape::Circle)]
+    #[case("a<Is it?>", "a", Some("Is it?"), NodeShape::Diamond)]
+    #[case("a{{A}}", "a", Some("A"), NodeShape::Hexagon)]
+    #[case("a[(Database)]", "a", Some("Database"), NodeShape::Cylinder)]
+    #[case("a[/Input/]", "a", Some("Input"), NodeShape::Parallelogram)]
+    #[case("a([Start])", "a", Some("Start"), NodeShape::Stadium)]
+    #[case("a[/usr/bin]", "a", Some("/usr/bin"), NodeShape::Square)]
+    #[case("a[(x)]", "a", Some("x"), NodeShape::Cylinder)]
+    #[case("a[(x) or y]", "a", Some("(x) or y"), NodeShape::Square)]
+    #[case("a(f(x) y)", "a", Some("f(x) y"), NodeShape::Rounded)]
+    #[case("a[A] -> b", "a", Some("A"), NodeShape::Square)]
     fn test_node_from_pair(
         #[case] input: &str,
         #[case] id: &str,
@@ -272,4 +574,58 @@ mod tests {
         assert_eq!(node.label, label.map(String::from));
         assert_eq!(node.shape, shape);
     }
+
+    #[rstest]
+    #[case("a[A)")]
+    #[case("a(A]")]
+    #[case("a[/A)")]
+    #[case("a{A]")]
+    #[case("a<A)")]
+    fn test_node_unpaired_delimiters(#[case] input: &str) {
+        use crate::parser::GraphParser;
+
+        let parsed = GraphParser::parse(Rule::node, input);
+        assert!(parsed.map_or(true, |mut pairs| pairs.next().unwrap().as_str() != input));
+    }
+
+    #[rstest]
+    #[case(NodeShape::Rounded)]
+    #[case(NodeShape::Square)]
+    #[case(NodeShape::Triangle)]
+    #[case(NodeShape::Circle)]
+    #[case(NodeShape::Diamond)]
+    #[case(NodeShape::Hexagon)]
+    #[case(NodeShape::Cylinder)]
+    #[case(NodeShape::Parallelogram)]
+    #[case(NodeShape::Stadium)]
+    #[case(NodeShape::Empty)]
+    fn test_nodeshape_fits_content(#[case] shape: NodeShape) {
+        let content = (80.0, 26.0);
+        let size = shape.size(content);
+        let (x, y) = shape.content_offset(content, size);
+
+        assert!(size.0 >= content.0 && size.1 >= content.1);
+        assert!(x >= 0.0 && y >= 0.0);
+        assert!(x + content.0 <= size.0 + 0.01 && y + content.1 <= size.1 + 0.01);
+    }
+
+    #[rstest]
+    #[case(NodeShape::Square, (1.0, 0.0), 50.0)]
+    #[case(NodeShape::Square, (0.0, 1.0), 20.0)]
+    #[case(NodeShape::Diamond, (1.0, 0.0), 50.0)]
+    #[case(NodeShape::Diamond, (0.6, 0.8), 19.23)]
+    #[case(NodeShape::Circle, (0.0, -1.0), 20.0)]
+    #[case(NodeShape::Triangle, (0.0, -1.0), 20.0)]
+    fn test_node_boundary_distance(
+        #[case] shape: NodeShape,
+        #[case] direction: (f32, f32),
+        #[case] expected: f32,
+    ) {
+        let mut node = Node::new("a");
+        node.shape = shape;
+        node.size = Some((100.0, 40.0));
+
+        let (dx, dy) = direction;
+        assert!((node.boundary_distance(dx, dy) - expected).abs() < 0.1);
+    }
 }
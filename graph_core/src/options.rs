@@ -0,0 +1,392 @@
+use std::sync::OnceLock;
+
+/// The font embedded in the generated documents and used to measure text by default
+pub(crate) const DEFAULT_FONT_FAMILY: &str = "JetBrains Mono";
+
+/// The colors used to draw a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    /// The color behind the graph, the background is transparent when it is not set
+    pub background: Option<String>,
+    pub text: String,
+    pub node_fill: String,
+    pub node_stroke: String,
+    /// The color of the lines and heads of edges
+    pub edge_stroke: String,
+    pub edge_label_fill: String,
+    pub cluster_fill: String,
+    pub cluster_stroke: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: None,
+            text: String::from("#000000"),
+            node_fill: String::from("#fcf9fa"),
+            node_stroke: String::from("#cecace"),
+            edge_stroke: String::from("#5d5b5d"),
+            edge_label_fill: String::from("#fcf9fa"),
+            cluster_fill: String::from("#f7f4f5"),
+            cluster_stroke: String::from("#cecace"),
+        }
+    }
+}
+
+impl Theme {
+    /// The names of the built-in themes, which can be selected with `theme:name` after the
+    /// direction of a graph
+    pub const NAMES: [&'static str; 5] =
+        ["default", "dark", "high-contrast", "monochrome", "pastel"];
+
+    /// The built-in theme with the given name, `print` is accepted for the monochrome theme
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "default" => Some(Theme::default()),
+            "dark" => Some(Theme::dark()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            "monochrome" | "print" => Some(Theme::monochrome()),
+            "pastel" => Some(Theme::pastel()),
+            _ => None,
+        }
+    }
+
+    /// Light text and outlines on a dark background
+    pub fn dark() -> Theme {
+        Theme {
+            background: Some(String::from("#1e1d20")),
+            text: String::from("#e8e6e9"),
+            node_fill: String::from("#2a292d"),
+            node_stroke: String::from("#4d4a50"),
+            edge_stroke: String::from("#a8a4aa"),
+            edge_label_fill: String::from("#1e1d20"),
+            cluster_fill: String::from("#252427"),
+            cluster_stroke: String::from("#3d3b40"),
+        }
+    }
+
+    /// Black on white with no shading
+    pub fn high_contrast() -> Theme {
+        Theme {
+            background: Some(String::from("#ffffff")),
+            text: String::from("#000000"),
+            node_fill: String::from("#ffffff"),
+            node_stroke: String::from("#000000"),
+            edge_stroke: String::from("#000000"),
+            edge_label_fill: String::from("#ffffff"),
+            cluster_fill: String::from("#ffffff"),
+            cluster_stroke: String::from("#000000"),
+        }
+    }
+
+    /// Shades of grey that print well without color
+    pub fn monochrome() -> Theme {
+        Theme {
+            background: None,
+            text: String::from("#000000"),
+            node_fill: String::from("#ffffff"),
+            node_stroke: String::from("#555555"),
+            edge_stroke: String::from("#333333"),
+            edge_label_fill: String::from("#ffffff"),
+            cluster_fill: String::from("#f2f2f2"),
+            cluster_stroke: String::from("#888888"),
+        }
+    }
+
+    /// Soft warm nodes with cool clusters
+    pub fn pastel() -> Theme {
+        Theme {
+            background: None,
+            text: String::from("#3b3541"),
+            node_fill: String::from("#fdf1e7"),
+            node_stroke: String::from("#efc5a5"),
+            edge_stroke: String::from("#8e7fa3"),
+            edge_label_fill: String::from("#fdf8f3"),
+            cluster_fill: String::from("#eef4fb"),
+            cluster_stroke: String::from("#bcd3ea"),
+        }
+    }
+
+    /// Style rules that draw the elements of a document with the theme, overriding the colors
+    /// they were drawn with
+    pub(crate) fn to_css(&self) -> String {
+        let background = self.background.as_deref().unwrap_or("none");
+        format!(
+            ".background {{ fill: {background}; }}
+            text {{ fill: {}; }}
+            .node-shape {{ fill: {}; stroke: {}; }}
+            .node-fill {{ fill: {}; }}
+            .node-line {{ stroke: {}; }}
+            .edge-line, .edge-head-line {{ stroke: {}; }}
+            .edge-head {{ fill: {}; }}
+            .edge-label {{ fill: {}; }}
+            .cluster-shape {{ fill: {}; stroke: {}; }}
+            ",
+            self.text,
+            self.node_fill,
+            self.node_stroke,
+            self.node_fill,
+            self.node_stroke,
+            self.edge_stroke,
+            self.edge_stroke,
+            self.edge_label_fill,
+            self.cluster_fill,
+            self.cluster_stroke,
+        )
+    }
+}
+
+/// The sizes of text and the spacing used to lay out a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutOptions {
+    /// The font used to measure and draw text, only the default font is embedded in the output
+    /// so any other font must be available wherever the graph is viewed
+    pub font_family: String,
+    pub id_font_size: f32,
+    pub label_font_size: f32,
+    pub edge_label_font_size: f32,
+    pub cluster_label_font_size: f32,
+    /// The space between the text of a node and its outline in the format (x, y)
+    pub node_padding: (f32, f32),
+    /// The space between the label of an edge and the outline of its background
+    pub edge_label_padding: (f32, f32),
+    /// The space left between the members of a cluster and its outline
+    pub cluster_padding: f32,
+    /// The distance between the centers of nodes on the same layer as a multiple of the size of
+    /// the largest node
+    pub node_spacing: f32,
+    /// The smallest space between nodes on the same layer
+    pub node_gap: f32,
+    /// The smallest space between layers
+    pub rank_gap: f32,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        LayoutOptions {
+            font_family: String::from(DEFAULT_FONT_FAMILY),
+            id_font_size: 6.0,
+            label_font_size: 8.0,
+            edge_label_font_size: 6.0,
+            cluster_label_font_size: 8.0,
+            node_padding: (10.0, 5.0),
+            edge_label_padding: (3.0, 2.0),
+            cluster_padding: 10.0,
+            node_spacing: 1.5,
+            node_gap: 30.0,
+            rank_gap: 60.0,
+        }
+    }
+}
+
+/// Options used to generate a document from a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// The options used to lay out the graph when it is parsed, a graph that has already been
+    /// laid out is drawn with the options it was laid out with
+    pub layout: LayoutOptions,
+    /// The theme used to draw the graph, overriding the theme selected by the graph. Without
+    /// either the default theme is used
+    pub theme: Option<Theme>,
+    /// A theme the document switches to when it is viewed with a dark color scheme preferred
+    pub dark_theme: Option<Theme>,
+    /// The space around the graph, by default it is 7.5% of the longest side of the graph and
+    /// at least 20
+    pub margin: Option<f32>,
+    /// A color drawn behind the graph, replacing the background of the theme
+    pub background: Option<String>,
+    /// The number of pixels per unit of the graph in raster images
+    pub scale: f32,
+    /// The quality of JPEG images from 1 to 100
+    pub quality: u8,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            layout: LayoutOptions::default(),
+            theme: None,
+            dark_theme: None,
+            margin: None,
+            background: None,
+            scale: 1.0,
+            quality: 90,
+        }
+    }
+}
+
+/// Options used to format the source of a graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatOptions {
+    /// The number of spaces statements are indented by for each level of nesting
+    pub indent: usize,
+    /// Move node statements to the start of each block and sort them by id. The order nodes are
+    /// declared in changes the order they are laid out in
+    pub sort_nodes: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 2,
+            sort_nodes: false,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// The theme to draw a graph with, the theme of the options wins over the one the graph
+    /// selects
+    pub(crate) fn theme_for<'a>(&'a self, graph_theme: Option<&'a Theme>) -> &'a Theme {
+        static DEFAULT: OnceLock<Theme> = OnceLock::new();
+        self.theme
+            .as_ref()
+            .or(graph_theme)
+            .unwrap_or_else(|| DEFAULT.get_or_init(Theme::default))
+    }
+
+    /// The theme the elements of a graph are drawn with
+    pub(crate) fn theme(&self) -> &Theme {
+        self.theme_for(None)
+    }
+
+    /// The space around a graph of the given size
+    pub(crate) fn margin(&self, (width, height): (f32, f32)) -> f32 {
+        self.margin
+            .unwrap_or_else(|| f32::max(20.0, f32::max(width, height) * 0.075))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_graph, generate_graph_with, parse, render_svg_with};
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(None, (100.0, 50.0), 20.0)]
+    #[case(None, (1000.0, 50.0), 75.0)]
+    #[case(Some(0.0), (1000.0, 50.0), 0.0)]
+    fn test_render_options_margin(
+        #[case] margin: Option<f32>,
+        #[case] size: (f32, f32),
+        #[case] expected: f32,
+    ) {
+        let options = RenderOptions {
+            margin,
+            ..RenderOptions::default()
+        };
+        assert_eq!(options.margin(size), expected);
+    }
+
+    #[test]
+    fn test_render_with_theme() {
+        let input = "graph\n  a[A] --> |label| b\n  subgraph c {\n    b\n  }";
+        let theme = Theme {
+            background: Some(String::from("#101010")),
+            text: String::from("#202020"),
+            node_fill: String::from("#303030"),
+            node_stroke: String::from("#404040"),
+            edge_stroke: String::from("#505050"),
+            edge_label_fill: String::from("#606060"),
+            cluster_fill: String::from("#707070"),
+            cluster_stroke: String::from("#808080"),
+        };
+        let options = RenderOptions {
+            theme: Some(theme.clone()),
+            ..RenderOptions::default()
+        };
+        let svg = generate_graph_with(input, &options).unwrap();
+
+        for color in [
+            theme.background.as_deref().unwrap(),
+            &theme.text,
+            &theme.node_fill,
+            &theme.node_stroke,
+            &theme.edge_stroke,
+            &theme.edge_label_fill,
+            &theme.cluster_fill,
+            &theme.cluster_stroke,
+        ] {
+            assert!(svg.contains(color), "{color} is not used");
+        }
+        assert!(!generate_graph(input).unwrap().contains("#101010"));
+    }
+
+    #[rstest]
+    #[case("graph\n  a(A) --> b(B)", None, Theme::default())]
+    #[case("graph theme:pastel\n  a(A) --> b(B)", None, Theme::pastel())]
+    #[case("graph\n  a(A) --> b(B)", Some(Theme::dark()), Theme::dark())]
+    #[case(
+        "graph theme:pastel\n  a(A) --> b(B)",
+        Some(Theme::dark()),
+        Theme::dark()
+    )]
+    fn test_render_theme_precedence(
+        #[case] input: &str,
+        #[case] theme: Option<Theme>,
+        #[case] expected: Theme,
+    ) {
+        let graph = parse(input).unwrap();
+        let options = RenderOptions {
+            theme,
+            ..RenderOptions::default()
+        };
+        let svg = render_svg_with(&graph, &options);
+
+        assert!(svg.contains(&format!("fill=\"{}\"", expected.node_fill)));
+        for other in [Theme::default(), Theme::pastel(), Theme::dark()] {
+            if other.node_fill != expected.node_fill {
+                assert!(
+                    !svg.contains(&other.node_fill),
+                    "{} is used",
+                    other.node_fill
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_with_font_family() {
+        let options = RenderOptions {
+            layout: LayoutOptions {
+                font_family: String::from("Helvetica"),
+                ..LayoutOptions::default()
+            },
+            ..RenderOptions::default()
+        };
+        let svg = generate_graph_with("graph\n  a --> b", &options).unwrap();
+        assert!(svg.contains("font-family: 'Helvetica'"));
+        assert!(!svg.contains("@font-face"));
+    }
+
+    #[test]
+    fn test_theme_named() {
+        for name in Theme::NAMES {
+            assert!(Theme::named(name).is_some(), "{name} is not a theme");
+        }
+        assert_eq!(Theme::named("default"), Some(Theme::default()));
+        assert_eq!(Theme::named("print"), Theme::named("monochrome"));
+        assert_eq!(Theme::named("neon"), None);
+    }
+
+    #[test]
+    fn test_render_with_dark_theme() {
+        let input = "graph theme:pastel\n  a(A) --> b(B)";
+        let svg = generate_graph(input).unwrap();
+        assert!(svg.contains(&Theme::pastel().node_fill));
+        assert!(!svg.contains("prefers-color-scheme"));
+
+        let options = RenderOptions {
+            dark_theme: Some(Theme::dark()),
+            ..RenderOptions::default()
+        };
+        let svg = generate_graph_with(input, &options).unwrap();
+        assert!(svg.contains("@media (prefers-color-scheme: dark)"));
+        assert!(svg.contains(&format!(
+            ".node-shape {{ fill: {};",
+            Theme::dark().node_fill
+        )));
+        assert!(svg.contains("class=\"background\""));
+    }
+}
@@ -1,7 +1,13 @@
+use std::collections::HashMap;
+
+use crate::cluster::Cluster;
 use crate::edge::Edge;
-use crate::graph::{Graph, GraphBuilder};
-use crate::node::Node;
-use pest::error::Error;
+use crate::error::{Error, SyntaxKind};
+use crate::graph::{Direction, Graph, GraphBuilder};
+use crate::node::{Node, NodeShape};
+use crate::options::{LayoutOptions, Theme};
+use crate::style::Style;
+use pest::iterators::Pair;
 use pest::Parser;
 use pest_derive::Parser;
 
@@ -9,25 +15,218 @@ use pest_derive::Parser;
 #[grammar = "graph.pest"]
 pub struct GraphParser;
 
-pub fn parse_from_string(contents: &str) -> Result<Graph, Error<Rule>> {
-    let pairs = match GraphParser::parse(Rule::graph, &contents) {
-        Ok(mut parse_result) => parse_result.next().unwrap().into_inner(),
+/// The result of parsing a graph, a partial graph is built from the statements that could be
+/// parsed even when there are errors
+#[derive(Debug)]
+pub struct ParseResult {
+    pub graph: Option<Graph>,
+    pub errors: Vec<Error>,
+}
+
+impl ParseResult {
+    /// The graph if it was parsed without errors, otherwise the first error
+    pub fn into_result(self) -> Result<Graph, Error> {
+        match (self.errors.into_iter().next(), self.graph) {
+            (Some(error), _) => Err(error),
+            (None, Some(graph)) => Ok(graph),
+            (None, None) => Err(Error::Layout {
+                message: String::from("no graph was produced"),
+            }),
+        }
+    }
+}
+
+/// Parses a graph, recovering from syntax errors by skipping the statement they occur in so
+/// every error in the input is reported at once
+pub fn parse_from_string(contents: &str) -> ParseResult {
+    parse_from_string_with(contents, &LayoutOptions::default())
+}
 
-        Err(e) => {
-            return Err(e);
+/// Parses a graph like [`parse_from_string`], laying it out with the given options
+pub fn parse_from_string_with(contents: &str, options: &LayoutOptions) -> ParseResult {
+    let (builder, mut errors) = parse_statements(contents, options, false);
+    let graph = builder.and_then(|mut builder| match builder.build() {
+        Ok(graph) => Some(graph),
+        Err(error) => {
+            errors.push(error);
+            None
         }
+    });
+    ParseResult { graph, errors }
+}
+
+/// Parses and validates a graph without laying it out or measuring its text, returning every
+/// error in the input. Unlike parsing, nodes defined again with a different label or shape and
+/// reused subgraph ids are reported as errors
+pub fn check_string(contents: &str) -> Vec<Error> {
+    parse_statements(contents, &LayoutOptions::default(), true).1
+}
+
+/// Adds the statements of the input to a builder. Statements with syntax errors are skipped, so
+/// the rest of the input is still added. Duplicate definitions are only reported if `duplicates`
+/// is set
+fn parse_statements(
+    contents: &str,
+    options: &LayoutOptions,
+    duplicates: bool,
+) -> (Option<GraphBuilder>, Vec<Error>) {
+    let graph_pair = match GraphParser::parse(Rule::graph, contents) {
+        Ok(mut pairs) => pairs
+            .next()
+            .expect("a graph is always parsed from the input"),
+        // Only an invalid definition of the graph can't be recovered from
+        Err(error) => return (None, vec![Error::from(error)]),
     };
 
+    let mut errors = syntax_errors(graph_pair.clone());
+    if duplicates {
+        find_duplicates(graph_pair.clone(), &mut errors);
+    }
+    let builder = build_graph(graph_pair, options, &mut errors);
+    (Some(builder), errors)
+}
+
+/// Finds the syntax errors in the statements that could not be parsed. The grammar skips to the
+/// end of the line of an invalid statement, so each one is parsed again on its own to find where
+/// the error in it is
+pub(crate) fn syntax_errors(graph_pair: Pair<Rule>) -> Vec<Error> {
+    let source = graph_pair.get_input();
+    let mut errors = Vec::new();
+    let mut definition_start = 0;
+
+    for pair in graph_pair.into_inner() {
+        match pair.as_rule() {
+            Rule::definition => definition_start = pair.as_span().start(),
+            // Text after the definition of the graph on the same line
+            Rule::invalid => {
+                let span = pest::Span::new(source, definition_start, pair.as_span().end());
+                errors.extend(span.and_then(|span| line_error(span, Rule::definition_line, false)));
+            }
+            Rule::statement => {
+                if let Some(statement_pair) = pair.into_inner().last() {
+                    statement_errors(statement_pair, false, &mut errors);
+                }
+            }
+            _ => {}
+        }
+    }
+    errors
+}
+
+/// Finds the syntax errors in a statement, which is inside a subgraph if `in_subgraph` is set
+fn statement_errors(statement_pair: Pair<Rule>, in_subgraph: bool, errors: &mut Vec<Error>) {
+    match statement_pair.as_rule() {
+        Rule::invalid => errors.extend(line_error(
+            statement_pair.as_span(),
+            Rule::statement_line,
+            in_subgraph,
+        )),
+        Rule::edge => {
+            for pair in statement_pair.into_inner() {
+                if pair.as_rule() == Rule::missing_target {
+                    let end = pair.as_span().end_pos();
+                    errors.push(Error::syntax(
+                        SyntaxKind::MissingTarget,
+                        "expected a node after the edge",
+                        end.span(&end),
+                    ));
+                }
+            }
+        }
+        Rule::subgraph => {
+            for pair in statement_pair.into_inner() {
+                match pair.as_rule() {
+                    Rule::subgraph_statement => {
+                        if let Some(statement_pair) = pair.into_inner().last() {
+                            statement_errors(statement_pair, true, errors);
+                        }
+                    }
+                    Rule::unclosed => {
+                        let end = pair.as_span().end_pos();
+                        errors.push(Error::syntax(
+                            SyntaxKind::UnclosedSubgraph,
+                            "expected `}` to close the subgraph",
+                            end.span(&end),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The syntax error in the text of the span when it is parsed with the rule on its own. None if
+/// the text can be parsed, which happens when a subgraph isn't closed on a line of its own, as the
+/// error is then on the line that should close it
+fn line_error(span: pest::Span, rule: Rule, in_subgraph: bool) -> Option<Error> {
+    match GraphParser::parse(rule, span.as_str()) {
+        Ok(_) if in_subgraph => Some(Error::syntax(
+            SyntaxKind::NestedSubgraph,
+            "a subgraph can't be inside another subgraph",
+            span,
+        )),
+        Ok(_) => None,
+        Err(error) => match Error::from(error) {
+            // The offsets are relative to the start of the span
+            Error::Syntax {
+                kind,
+                message,
+                span: error_span,
+                ..
+            } => {
+                let source = span.get_input();
+                let start = span.start() + error_span.start;
+                let end = span.start() + error_span.end;
+                pest::Span::new(source, start, end).map(|span| Error::syntax(kind, message, span))
+            }
+            error => Some(error),
+        },
+    }
+}
+
+/// Finds the built-in theme selected in the definition of the graph
+fn parse_theme(pair: Pair<Rule>) -> Result<Theme, Error> {
+    let span = pair.as_span();
+    let name = pair.into_inner().as_str();
+    Theme::named(name).ok_or_else(|| {
+        Error::invalid_attribute(
+            "theme",
+            format!(
+                "unknown theme `{name}`, expected one of {}",
+                Theme::NAMES.join(", ")
+            ),
+            span,
+        )
+    })
+}
+
+/// Adds the statements of the graph to a builder, recording the statements that could not be
+/// added
+fn build_graph(
+    graph_pair: Pair<Rule>,
+    options: &LayoutOptions,
+    errors: &mut Vec<Error>,
+) -> GraphBuilder {
     let mut builder = GraphBuilder::new();
+    builder.set_layout_options(options.clone());
+    let mut styles = Vec::new();
 
-    for graph_pair in pairs {
+    for graph_pair in graph_pair.into_inner() {
         match graph_pair.as_rule() {
             Rule::definition => {
                 for definition_pair in graph_pair.into_inner() {
                     match definition_pair.as_rule() {
                         Rule::direction => {
-                            // println!("Direction: {}", definition_pair.as_str())
+                            builder.set_direction(Direction::from(definition_pair.as_str()));
                         }
+                        Rule::theme => match parse_theme(definition_pair) {
+                            Ok(theme) => {
+                                builder.set_theme(Some(theme));
+                            }
+                            Err(error) => errors.push(error),
+                        },
                         _ => {}
                     }
                 }
@@ -35,39 +234,16 @@ pub fn parse_from_string(contents: &str) -> Result<Graph, Error<Rule>> {
             Rule::statement => {
                 for statement_pair in graph_pair.into_inner() {
                     match statement_pair.as_rule() {
-                        Rule::node => {
-                            builder.insert_or_update_node(Node::from(statement_pair));
+                        Rule::subgraph => {
+                            parse_subgraph(&mut builder, statement_pair, &mut styles, errors)
                         }
-                        Rule::edge => {
-                            let mut source_id = None;
-                            let mut target_id = None;
-                            for pair in statement_pair.clone().into_inner() {
-                                match pair.as_rule() {
-                                    Rule::node => {
-                                        if let Some(tag) = pair.as_node_tag() {
-                                            let node = Node::from(pair.clone());
-                                            let node_id = node.id.clone();
-                                            match tag {
-                                                "source" => source_id = Some(node_id),
-                                                "target" => target_id = Some(node_id),
-                                                _ => {}
-                                            }
-                                            builder.insert_node(node);
-                                        }
-                                    }
-                                    _ => {}
-                                }
-                            }
-
-                            match (source_id, target_id) {
-                                (Some(source), Some(target)) => {
-                                    let edge = Edge::from(statement_pair);
-                                    builder.add_edge(source, target, edge);
-                                }
-                                _ => panic!("Edge must have source and target nodes"),
+                        _ => {
+                            if let Err(error) =
+                                parse_statement(&mut builder, statement_pair, &mut styles)
+                            {
+                                errors.push(error);
                             }
                         }
-                        _ => {}
                     }
                 }
             }
@@ -75,8 +251,476 @@ pub fn parse_from_string(contents: &str) -> Result<Graph, Error<Rule>> {
             _ => {}
         }
     }
+    apply_styles(&mut builder, styles, errors);
+    builder
+}
+
+/// Records nodes that are defined again with a different label or shape, and subgraphs that
+/// reuse the id of another subgraph. A node can be defined the same way any number of times.
+/// Rendering allows both, the last definition of a node wins and each subgraph is drawn
+fn find_duplicates(graph_pair: Pair<Rule>, errors: &mut Vec<Error>) {
+    let mut nodes: HashMap<String, (Node, pest::Span)> = HashMap::new();
+    let mut subgraphs: HashMap<String, pest::Span> = HashMap::new();
+
+    for pair in graph_pair.into_inner().flatten() {
+        let span = pair.as_span();
+        match pair.as_rule() {
+            Rule::node => {
+                let node = Node::from(pair);
+                // A bare id only refers to a node
+                if node.shape == NodeShape::Empty {
+                    continue;
+                }
+                match nodes.get(&node.id) {
+                    Some((first, previous))
+                        if (&first.label, &first.shape) != (&node.label, &node.shape) =>
+                    {
+                        errors.push(Error::duplicate("node", &node.id, *previous, span));
+                    }
+                    Some(_) => {}
+                    None => {
+                        nodes.insert(node.id.clone(), (node, span));
+                    }
+                }
+            }
+            Rule::subgraph => {
+                let id = Cluster::from(pair).id;
+                match subgraphs.get(&id) {
+                    Some(previous) => {
+                        errors.push(Error::duplicate("subgraph", &id, *previous, span))
+                    }
+                    None => {
+                        subgraphs.insert(id, span);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Adds a node or edge statement to the builder, returning the ids of the nodes it mentions
+fn parse_statement<'a>(
+    builder: &mut GraphBuilder,
+    statement_pair: Pair<'a, Rule>,
+    styles: &mut Vec<Pair<'a, Rule>>,
+) -> Result<Vec<String>, Error> {
+    match statement_pair.as_rule() {
+        Rule::node => {
+            let node = Node::from(statement_pair);
+            let node_id = node.id.clone();
+            builder.insert_or_update_node(node);
+            Ok(vec![node_id])
+        }
+        Rule::edge => {
+            for pair in statement_pair.clone().into_inner() {
+                if pair.as_rule() == Rule::node && pair.as_node_tag().is_some() {
+                    builder.insert_node(Node::from(pair));
+                }
+            }
+
+            let edge = Edge::from(statement_pair);
+            // An edge without a target is a syntax error, only its source is added
+            if edge.target.is_empty() {
+                return Ok(vec![edge.source]);
+            }
+            let node_ids = vec![edge.source.clone(), edge.target.clone()];
+            builder.add_edge(edge);
+            Ok(node_ids)
+        }
+        // Styles are applied once every node and edge has been added
+        Rule::style | Rule::link_style | Rule::class_def | Rule::class => {
+            styles.push(statement_pair);
+            Ok(Vec::new())
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Adds a subgraph and the statements inside it to the builder
+fn parse_subgraph<'a>(
+    builder: &mut GraphBuilder,
+    subgraph_pair: Pair<'a, Rule>,
+    styles: &mut Vec<Pair<'a, Rule>>,
+    errors: &mut Vec<Error>,
+) {
+    let mut cluster = Cluster::from(subgraph_pair.clone());
+
+    for pair in subgraph_pair.into_inner() {
+        if pair.as_rule() == Rule::subgraph_statement {
+            for statement_pair in pair.into_inner() {
+                match parse_statement(builder, statement_pair, styles) {
+                    Ok(node_ids) => cluster.nodes.extend(node_ids),
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+    }
 
-    let graph = builder.build();
+    builder.add_cluster(cluster);
+}
 
-    Ok(graph)
+/// Applies the style statements to the nodes and edges they refer to, so elements can be styled
+/// before they are declared. Classes are applied first so they can be overridden with `style`
+fn apply_styles(builder: &mut GraphBuilder, styles: Vec<Pair<Rule>>, errors: &mut Vec<Error>) {
+    let mut class_defs: HashMap<&str, Style> = HashMap::new();
+    for pair in styles
+        .iter()
+        .filter(|pair| pair.as_rule() == Rule::class_def)
+    {
+        let mut style = Style::default();
+        let mut name = "";
+        for class_pair in pair.clone().into_inner() {
+            match class_pair.as_rule() {
+                Rule::class_name => name = class_pair.as_str(),
+                Rule::style_properties => parse_style(&mut style, class_pair, errors),
+                _ => {}
+            }
+        }
+        class_defs.entry(name).or_default().merge(&style);
+    }
+
+    for pair in styles.iter().filter(|pair| pair.as_rule() == Rule::class) {
+        let pairs = pair.clone().into_inner().collect::<Vec<_>>();
+        let Some(name_pair) = pairs.iter().find(|pair| pair.as_rule() == Rule::class_name) else {
+            continue;
+        };
+        let Some(style) = class_defs.get(name_pair.as_str()) else {
+            errors.push(Error::invalid_attribute(
+                "class",
+                format!(
+                    "class `{}` is not defined with classDef",
+                    name_pair.as_str()
+                ),
+                name_pair.as_span(),
+            ));
+            continue;
+        };
+        for id_pair in pairs.iter().filter(|pair| pair.as_rule() == Rule::id) {
+            match builder.node_mut(id_pair.as_str()) {
+                Some(node) => node.style.merge(style),
+                None => errors.push(Error::unknown_node(id_pair.as_str(), id_pair.as_span())),
+            }
+        }
+    }
+
+    for pair in styles.iter().filter(|pair| pair.as_rule() == Rule::style) {
+        let mut style = Style::default();
+        let mut id_pair = None;
+        for style_pair in pair.clone().into_inner() {
+            match style_pair.as_rule() {
+                Rule::id => id_pair = Some(style_pair),
+                Rule::style_properties => parse_style(&mut style, style_pair, errors),
+                _ => {}
+            }
+        }
+        if let Some(id_pair) = id_pair {
+            match builder.node_mut(id_pair.as_str()) {
+                Some(node) => node.style.merge(&style),
+                None => errors.push(Error::unknown_node(id_pair.as_str(), id_pair.as_span())),
+            }
+        }
+    }
+
+    for pair in styles
+        .iter()
+        .filter(|pair| pair.as_rule() == Rule::link_style)
+    {
+        let mut style = Style::default();
+        let mut index_pairs = Vec::new();
+        for style_pair in pair.clone().into_inner() {
+            match style_pair.as_rule() {
+                Rule::edge_index => index_pairs.push(style_pair),
+                Rule::style_properties => parse_style(&mut style, style_pair, errors),
+                _ => {}
+            }
+        }
+        let edges = builder.edges_mut();
+        let edge_count = edges.len();
+        for index_pair in index_pairs {
+            let edge = index_pair
+                .as_str()
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| edges.get_mut(index));
+            match edge {
+                Some(edge) => edge.style.merge(&style),
+                None => errors.push(Error::invalid_attribute(
+                    "linkStyle",
+                    format!(
+                        "there is no edge {}, the graph has {edge_count} edges numbered from 0",
+                        index_pair.as_str()
+                    ),
+                    index_pair.as_span(),
+                )),
+            }
+        }
+    }
+}
+
+/// Sets the properties of a style statement, recording the properties that are not valid
+fn parse_style(style: &mut Style, properties_pair: Pair<Rule>, errors: &mut Vec<Error>) {
+    for property_pair in properties_pair.into_inner() {
+        let span = property_pair.as_span();
+        let (mut name, mut value) = ("", "");
+        for pair in property_pair.into_inner() {
+            match pair.as_rule() {
+                Rule::style_name => name = pair.as_str(),
+                Rule::style_value => value = pair.as_str(),
+                _ => {}
+            }
+        }
+        if let Err(message) = style.set(name, value) {
+            errors.push(Error::invalid_attribute(name, message, span));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::time::{Duration, Instant};
+
+    /// Counts the node and edge statements in the input, including those inside subgraphs
+    fn count_statements(input: &str) -> (usize, usize) {
+        let pairs = GraphParser::parse(Rule::graph, input).unwrap().flatten();
+        let statements = pairs
+            .filter(|pair| matches!(pair.as_rule(), Rule::statement | Rule::subgraph_statement))
+            .filter_map(|pair| pair.into_inner().last())
+            .map(|pair| pair.as_rule())
+            .collect::<Vec<_>>();
+
+        let count = |rule| statements.iter().filter(|&&other| other == rule).count();
+        (count(Rule::node), count(Rule::edge))
+    }
+
+    #[rstest]
+    #[case("graph\n  a --> b // an edge", (0, 1))]
+    #[case("graph\n  a --> b %% an edge", (0, 1))]
+    #[case("graph\n  a --> b /* an edge */", (0, 1))]
+    #[case("graph\n  a[A] // a node\n  b", (2, 0))]
+    #[case("graph\n  // a --> b\n  c --> d", (0, 1))]
+    #[case("graph\n  %% a --> b\n  c --> d", (0, 1))]
+    #[case("graph\n  /* a --> b\n  c --> d */\n  e", (1, 0))]
+    #[case("graph\n  a /* the source */ --> /* the target */ b", (0, 1))]
+    #[case("// a title\ngraph down // a direction\n  a --> b\n", (0, 1))]
+    #[case("graph\n  a --> b\n\n  // trailing\n", (0, 1))]
+    #[case("graph\n  subgraph s { // a subgraph\n    a --> b // an edge\n    // c\n  }", (0, 1))]
+    fn test_comments(#[case] input: &str, #[case] expected: (usize, usize)) {
+        assert_eq!(count_statements(input), expected);
+    }
+
+    #[rstest]
+    #[case("graph\n  a[http://example.com] --> b", "http://example.com")]
+    #[case("graph\n  a --> |50%% done| b", "50%% done")]
+    #[case("graph\n  a[/*/] --> b", "*")]
+    fn test_comments_in_labels(#[case] input: &str, #[case] label: &str) {
+        let labels = GraphParser::parse(Rule::graph, input)
+            .unwrap()
+            .flatten()
+            .filter(|pair| matches!(pair.as_rule(), Rule::node_label | Rule::edge_label))
+            .map(|pair| pair.as_str().trim_matches('|').to_string())
+            .collect::<Vec<_>>();
+        assert!(labels.contains(&label.to_string()));
+    }
+
+    #[rstest]
+    #[case("graph\n  a --> b /* unterminated")]
+    #[case("graph\n  a --> b / not a comment")]
+    #[case("graph\n  a -->// b")]
+    fn test_comments_invalid(#[case] input: &str) {
+        assert_eq!(parse_from_string(input).errors.len(), 1);
+    }
+
+    #[rstest]
+    #[case("graph\n  a --> b", &[])]
+    #[case("graph\n  a -> b\n  c --> d", &[(2, 5)])]
+    #[case("graph\n  a -> b\n  c --> d\n  e[E\n  f --> g", &[(2, 5), (4, 4)])]
+    #[case("graph\n  !\n  a --> b\n  ?\n  c", &[(2, 3), (4, 3)])]
+    #[case("graph down extra\n  a --> b", &[(1, 12)])]
+    #[case("graph\n  a --> b\n  subgraph s {\n    c -> d\n    e\n  }", &[(4, 7)])]
+    #[case("graph\n  a --> b\n  subgraph s {\n    c\n", &[(5, 1)])]
+    fn test_recover_errors(#[case] input: &str, #[case] expected: &[(usize, usize)]) {
+        let result = parse_from_string(input);
+        let locations = result
+            .errors
+            .iter()
+            .filter_map(|error| error.location())
+            .map(|location| (location.line, location.column))
+            .collect::<Vec<_>>();
+
+        assert_eq!(locations, expected);
+        assert!(result.graph.is_some());
+    }
+
+    #[rstest]
+    #[case("graph\n  subgraph s {\n    c -> d\n    e\n  }", &["e"])]
+    #[case("graph\n  subgraph s {\n    !\n    c --> d\n  }", &["c", "d"])]
+    #[case("graph\n  a --> b\n  subgraph s {\n    c\n", &["c"])]
+    fn test_recover_subgraph(#[case] input: &str, #[case] expected: &[&str]) {
+        let result = parse_from_string(input);
+        assert_eq!(result.errors.len(), 1);
+        let graph = result.graph.unwrap();
+        assert_eq!(graph.clusters().len(), 1);
+        assert_eq!(graph.clusters()[0].nodes, expected);
+    }
+
+    #[test]
+    fn test_recover_nested_subgraph() {
+        let input = "graph\n  subgraph s {\n    subgraph t {\n      a\n    }\n  }";
+        let errors = parse_from_string(input).errors;
+        assert!(errors[0]
+            .to_string()
+            .starts_with("3:5: a subgraph can't be inside"));
+    }
+
+    #[test]
+    fn test_recover_many_errors() {
+        let input = format!("graph\n{}  a --> b", "  a -> b\n".repeat(2000));
+        let result = parse_from_string(&input);
+        assert_eq!(result.errors.len(), 2000);
+        assert_eq!(result.errors[1999].location().unwrap().line, 2001);
+        assert!(result.graph.is_some());
+    }
+
+    /// How long checking a graph with the given number of node declarations takes
+    fn check_time(nodes: usize) -> Duration {
+        let input = (0..nodes).fold(String::from("graph"), |input, index| {
+            input + &format!("\n  n{index}[N{index}]")
+        });
+        let start = Instant::now();
+        check_string(&input);
+        start.elapsed()
+    }
+
+    #[test]
+    fn test_check_time_linear() {
+        // Four times the input should take about four times as long, not sixteen
+        check_time(1000);
+        let (small, large) = (check_time(1000), check_time(4000));
+        assert!(
+            large < small * 8,
+            "{small:?} for 1000 nodes, {large:?} for 4000"
+        );
+    }
+
+    #[rstest]
+    #[case("grph\n  a --> b")]
+    #[case("\n  a --> b")]
+    fn test_recover_invalid_definition(#[case] input: &str) {
+        let result = parse_from_string(input);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.graph.is_none());
+    }
+
+    #[rstest]
+    #[case("graph\n  a --> b", None)]
+    #[case("graph theme:dark\n  a --> b", Some(Theme::dark()))]
+    #[case("graph left theme:print\n  a --> b", Some(Theme::monochrome()))]
+    fn test_theme(#[case] input: &str, #[case] expected: Option<Theme>) {
+        let graph = parse_from_string(input).into_result().unwrap();
+        assert_eq!(graph.theme(), expected.as_ref());
+    }
+
+    #[test]
+    fn test_theme_unknown() {
+        let result = parse_from_string("graph theme:neon\n  a --> b");
+        assert!(result.graph.unwrap().theme().is_none());
+        assert!(matches!(
+            &result.errors[..],
+            [Error::InvalidAttribute { name, location, .. }]
+                if name == "theme" && location.column == 7
+        ));
+    }
+
+    #[rstest]
+    #[case("graph\n  a[A] --> b\n  a[A] --> c\n  a", &[])]
+    #[case("graph\n  a[A] --> b\n  a(A) --> c", &[(3, 3, (2, 3))])]
+    #[case("graph\n  a[A]\n  subgraph s {\n    a[B]\n  }", &[(4, 5, (2, 3))])]
+    #[case(
+        "graph\n  subgraph s {\n    a\n  }\n  subgraph s {\n    b\n  }",
+        &[(5, 3, (2, 3))]
+    )]
+    fn test_duplicates(#[case] input: &str, #[case] expected: &[(usize, usize, (usize, usize))]) {
+        assert!(parse_from_string(input).errors.is_empty());
+        let errors = check_string(input)
+            .into_iter()
+            .map(|error| match error {
+                Error::Duplicate {
+                    location, previous, ..
+                } => (
+                    location.line,
+                    location.column,
+                    (previous.line, previous.column),
+                ),
+                error => panic!("unexpected error {error}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(errors, expected);
+    }
+
+    #[rstest]
+    #[case("graph\n  a[A] --> b\n  style a fill:#f9f", 0)]
+    #[case("graph\n  a -->\n  a[A]\n  a[B]\n  class a missing", 3)]
+    fn test_check(#[case] input: &str, #[case] expected: usize) {
+        let errors = check_string(input);
+        assert_eq!(errors.len(), expected, "{errors:?}");
+        for error in parse_from_string(input).errors {
+            assert!(errors.contains(&error), "{error}");
+        }
+    }
+
+    #[test]
+    fn test_styles() {
+        let input = "graph
+  style a fill:#f9f,stroke:#333
+  classDef warn fill:#ff0,color:#700,font-weight:bold
+  a --> b
+  subgraph s {
+    c
+    class b,c warn
+  }
+  style b fill:#0f0
+  linkStyle 0 stroke:#e33,stroke-width:2";
+        let graph = parse_from_string(input).into_result().unwrap();
+
+        let a = &graph.node("a").unwrap().style;
+        assert_eq!(a.fill.as_deref(), Some("#f9f"));
+        assert_eq!(a.stroke.as_deref(), Some("#333"));
+        let b = &graph.node("b").unwrap().style;
+        assert_eq!(b.fill.as_deref(), Some("#0f0"));
+        assert_eq!(b.color.as_deref(), Some("#700"));
+        let c = &graph.node("c").unwrap().style;
+        assert_eq!(c.fill.as_deref(), Some("#ff0"));
+        assert_eq!(c.font_weight.as_deref(), Some("bold"));
+        assert_eq!(graph.clusters()[0].nodes, vec!["c"]);
+
+        let edge = &graph.edges()[0];
+        assert_eq!(edge.style.stroke.as_deref(), Some("#e33"));
+        assert_eq!(edge.style.stroke_width, Some(2.0));
+    }
+
+    #[rstest]
+    #[case("graph\n  a\n  style b fill:#fff", "3:9: unknown node `b`")]
+    #[case(
+        "graph\n  a\n  style a background:#fff",
+        "invalid attribute `background`"
+    )]
+    #[case(
+        "graph\n  a\n  style a stroke-width:thick",
+        "invalid attribute `stroke-width`"
+    )]
+    #[case("graph\n  a\n  class a warn", "class `warn` is not defined")]
+    #[case("graph\n  a --> b\n  linkStyle 1 stroke:#fff", "there is no edge 1")]
+    fn test_styles_invalid(#[case] input: &str, #[case] expected: &str) {
+        let result = parse_from_string(input);
+        assert!(result.graph.is_some());
+        assert_eq!(result.errors.len(), 1);
+        assert!(
+            result.errors[0].to_string().contains(expected),
+            "{}",
+            result.errors[0]
+        );
+    }
 }
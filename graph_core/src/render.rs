@@ -1,21 +1,55 @@
-use resvg::usvg::{self, Options};
+use resvg::usvg::{self, fontdb, Options};
+use std::sync::{Arc, OnceLock};
 use svg::Node;
 
+use crate::{options::RenderOptions, style::Style};
+
 pub(crate) trait ToSvg<T>
 where
     T: Into<Box<dyn Node>>,
 {
-    fn to_svg(&mut self) -> T;
+    fn to_svg(&mut self, options: &RenderOptions) -> T;
 }
 
 use usvg::Tree;
 
-pub(crate) fn measure_text_width(content: &str, font_size: f32) -> (f32, f32) {
-    let mut opt = Options::default();
-    let font_data = include_bytes!("../fonts/JetBrainsMono-Light.ttf");
-    opt.fontdb_mut().load_system_fonts();
-    opt.fontdb_mut().load_font_data(font_data.to_vec());
-    opt.font_family = "JetBrains Mono".to_string();
+/// Sets the inline style of an element to the properties of the style that are set, which take
+/// precedence over the colors of the theme
+pub(crate) fn with_style<T: Node>(mut element: T, style: &Style, properties: &[&str]) -> T {
+    if let Some(declarations) = style.declarations(properties) {
+        element.assign("style", declarations);
+    }
+    element
+}
+
+/// The font embedded in generated documents
+pub(crate) const FONT_DATA: &[u8] = include_bytes!("../fonts/JetBrainsMono-Light.ttf");
+
+/// The system fonts and the embedded font. Loading the system fonts is slow, so they are only
+/// loaded once and shared by every document that is read
+fn fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            fonts.load_font_data(FONT_DATA.to_vec());
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+/// Options for reading documents with the system fonts and the embedded font available
+pub(crate) fn usvg_options(font_family: &str) -> Options<'static> {
+    Options {
+        fontdb: fonts(),
+        font_family: font_family.to_string(),
+        ..Options::default()
+    }
+}
+
+pub(crate) fn measure_text_width(content: &str, font_size: f32, font_family: &str) -> (f32, f32) {
+    let opt = usvg_options(font_family);
 
     let tree = match Tree::from_str(
         format!(
@@ -24,18 +58,24 @@ pub(crate) fn measure_text_width(content: &str, font_size: f32) -> (f32, f32) {
                 <text font-size="{}px">{}</text></g>
             </svg>
             "###,
-            font_size, content
+            font_size,
+            escape(content)
         )
         .as_str(),
         &opt,
     ) {
         Ok(tree) => tree,
-        Err(e) => {
-            eprintln!("Error: {}", e);
-            panic!("Failed to create SVG tree");
-        }
+        Err(_) => return (0.0, 0.0),
     };
 
     let root = tree.root();
     (root.bounding_box().width(), root.bounding_box().height())
 }
+
+/// Escapes the characters that have a special meaning in XML text
+fn escape(content: &str) -> String {
+    content
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,160 @@
+/// Colors and line styles that override the theme for a single node or edge, declared with
+/// `style id fill:#f9f,stroke:#333` or shared between elements with `classDef` and `class`
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Style {
+    /// The background of a node or of the label of an edge
+    pub fill: Option<String>,
+    /// The color of the outline of a node or the line of an edge
+    pub stroke: Option<String>,
+    pub stroke_width: Option<f32>,
+    /// The lengths of the dashes and gaps of the outline or line, such as `5 3`
+    pub stroke_dasharray: Option<String>,
+    /// The color of the text
+    pub color: Option<String>,
+    pub font_weight: Option<String>,
+}
+
+impl Style {
+    /// The names of the properties that can be set in a style statement
+    pub const PROPERTIES: [&'static str; 6] = [
+        "fill",
+        "stroke",
+        "stroke-width",
+        "stroke-dasharray",
+        "color",
+        "font-weight",
+    ];
+
+    /// Sets a property from its name and value in a style statement, returning a description of
+    /// the problem when either is invalid
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(String::from("expected a value"));
+        }
+        if value.contains(['"', '<', '>', ';']) {
+            return Err(format!("`{value}` is not a valid value"));
+        }
+
+        match name {
+            "fill" => self.fill = Some(String::from(value)),
+            "stroke" => self.stroke = Some(String::from(value)),
+            "stroke-width" => {
+                let width = value
+                    .trim_end_matches("px")
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|width| width.is_finite() && *width >= 0.0)
+                    .ok_or_else(|| format!("expected a width in pixels, found `{value}`"))?;
+                self.stroke_width = Some(width);
+            }
+            "stroke-dasharray" => self.stroke_dasharray = Some(String::from(value)),
+            "color" => self.color = Some(String::from(value)),
+            "font-weight" => match value {
+                "normal" | "bold" | "bolder" | "lighter" | "100" | "200" | "300" | "400"
+                | "500" | "600" | "700" | "800" | "900" => {
+                    self.font_weight = Some(String::from(value))
+                }
+                _ => return Err(format!("`{value}` is not a font weight")),
+            },
+            _ => return Err(format!("expected one of {}", Style::PROPERTIES.join(", "))),
+        }
+        Ok(())
+    }
+
+    /// Sets the properties that are set in `other`, keeping the rest
+    pub fn merge(&mut self, other: &Style) {
+        let Style {
+            fill,
+            stroke,
+            stroke_width,
+            stroke_dasharray,
+            color,
+            font_weight,
+        } = other.clone();
+        self.fill = fill.or(self.fill.take());
+        self.stroke = stroke.or(self.stroke.take());
+        self.stroke_width = stroke_width.or(self.stroke_width);
+        self.stroke_dasharray = stroke_dasharray.or(self.stroke_dasharray.take());
+        self.color = color.or(self.color.take());
+        self.font_weight = font_weight.or(self.font_weight.take());
+    }
+
+    /// The declarations for an inline `style` attribute that apply the given properties, which
+    /// take precedence over the theme and any style sheet. Returns None if none of them are set
+    pub(crate) fn declarations(&self, properties: &[&str]) -> Option<String> {
+        let declarations = properties
+            .iter()
+            .filter_map(|&property| {
+                let value = match property {
+                    "fill" => self.fill.clone(),
+                    "stroke" => self.stroke.clone(),
+                    "stroke-width" => self.stroke_width.map(|width| width.to_string()),
+                    "stroke-dasharray" => self.stroke_dasharray.clone(),
+                    // Text is drawn with its fill
+                    "color" => return self.color.as_ref().map(|color| format!("fill:{color}")),
+                    "font-weight" => self.font_weight.clone(),
+                    _ => None,
+                };
+                value.map(|value| format!("{property}:{value}"))
+            })
+            .collect::<Vec<_>>();
+
+        match declarations.is_empty() {
+            true => None,
+            false => Some(declarations.join(";")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("fill", "#f9f", Style { fill: Some(String::from("#f9f")), ..Style::default() })]
+    #[case("stroke-width", "2px", Style { stroke_width: Some(2.0), ..Style::default() })]
+    #[case(
+        "stroke-dasharray",
+        "5 3",
+        Style { stroke_dasharray: Some(String::from("5 3")), ..Style::default() }
+    )]
+    #[case("font-weight", "bold", Style { font_weight: Some(String::from("bold")), ..Style::default() })]
+    fn test_style_set(#[case] name: &str, #[case] value: &str, #[case] expected: Style) {
+        let mut style = Style::default();
+        style.set(name, value).unwrap();
+        assert_eq!(style, expected);
+    }
+
+    #[rstest]
+    #[case("background", "#fff")]
+    #[case("stroke-width", "thick")]
+    #[case("stroke-width", "-1")]
+    #[case("font-weight", "heavy")]
+    #[case("fill", "red\" onload=\"")]
+    fn test_style_set_invalid(#[case] name: &str, #[case] value: &str) {
+        assert!(Style::default().set(name, value).is_err());
+    }
+
+    #[test]
+    fn test_style_merge_and_declarations() {
+        let mut style = Style::default();
+        style.set("fill", "#f9f").unwrap();
+        style.set("stroke", "#333").unwrap();
+        let mut other = Style::default();
+        other.set("stroke", "#000").unwrap();
+        other.set("color", "#fff").unwrap();
+        style.merge(&other);
+
+        assert_eq!(
+            style.declarations(&["fill", "stroke", "stroke-width"]),
+            Some(String::from("fill:#f9f;stroke:#000"))
+        );
+        assert_eq!(
+            style.declarations(&["color", "font-weight"]),
+            Some(String::from("fill:#fff"))
+        );
+        assert_eq!(style.declarations(&["stroke-dasharray"]), None);
+    }
+}
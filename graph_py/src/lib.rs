@@ -1,5 +1,4 @@
-use graph_core;
-use pyo3::{exceptions::PyValueError, prelude::*};
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyBytes};
 
 /// Formats the sum of two numbers as string.
 #[pyfunction]
@@ -11,16 +10,33 @@ fn sum_as_string(a: usize, b: usize) -> PyResult<String> {
 fn graph_from_string(path: String) -> PyResult<String> {
     match graph_core::generate_graph(&path) {
         Ok(result) => Ok(result),
-        Err(_) => Err(PyValueError::new_err("Error from graph")),
+        Err(error) => Err(PyValueError::new_err(error.to_string())),
     }
 }
 
+/// Renders a graph in the format with the given extension, such as "svg", "png" or "webp"
+#[pyfunction]
+#[pyo3(signature = (contents, format = "svg"))]
+fn render_graph<'py>(
+    py: Python<'py>,
+    contents: &str,
+    format: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let format = graph_core::Format::from_extension(format)
+        .ok_or_else(|| PyValueError::new_err(format!("unknown format `{format}`")))?;
+    let document = graph_core::parse(contents)
+        .and_then(|graph| graph_core::render(&graph, format, &graph_core::RenderOptions::default()))
+        .map_err(|error| PyValueError::new_err(error.to_string()))?;
+    Ok(PyBytes::new_bound(py, &document))
+}
+
 /// A Python module implemented in Rust. The name of this function must match
 /// the `lib.name` setting in the `Cargo.toml`, else Python will not be able to
 /// import the module.
 #[pymodule]
 fn graph(module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(graph_from_string, module)?)?;
+    module.add_function(wrap_pyfunction!(render_graph, module)?)?;
     module.add_function(wrap_pyfunction!(sum_as_string, module)?)?;
     Ok(())
 }